@@ -29,7 +29,7 @@ impl BufferQueue {
         }
     }
 
-    pub fn pop(&self) -> Buffer {
+    pub fn pop(&self) -> Buffer<'_> {
         let id = self.await_next_id();
         Buffer {
             id,
@@ -61,7 +61,7 @@ pub struct Buffer<'a> {
 }
 
 impl<'a> Buffer<'a> {
-    pub fn borrow(&self) -> MutexGuard<String> {
+    pub fn borrow(&self) -> MutexGuard<'_, String> {
         self.inner.lock().unwrap()
     }
 
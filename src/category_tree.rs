@@ -0,0 +1,400 @@
+/*
+Category trees from the “categorylinks” table, i.e. categories along with their subcategories down
+to a given depth, and the pages within them. The category graph may contain cycles, so each
+category is entered into a tree only once. Link counts of pages may also be rolled up into totals
+per category (tree).
+*/
+use crate::{
+    categorylinks_table::{CategoryRow, CategorylinksParser},
+    link_count::LinkCount,
+    page_table::PageIndex,
+    pagelinks_table::{pl_query, target_query, PageLinks},
+    results::{Columns, Value},
+    sql_dump::{self, Aggregator},
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::Result;
+
+use std::io::Read;
+
+const CATEGORY_NS: PageNs = PageNs(14);
+
+pub type CategoryTree = AHashSet<(PageNs, PageTitle)>;
+
+// Page IDs of the subcategories of each category
+#[derive(Default)]
+pub struct Subcategories(AHashMap<PageTitle, Vec<PageId>>);
+
+impl Subcategories {
+    /*
+    Categories within the trees below the given roots, down to ‘max_depth’ levels of subcategories.
+    Subcategory IDs are resolved through the index, which must include the category namespace.
+    */
+    pub fn tree(&self, roots: &[PageTitle], max_depth: u32, index: &PageIndex) -> CategoryTree {
+        let mut tree: CategoryTree = roots
+            .iter()
+            .map(|title| (CATEGORY_NS, title.clone()))
+            .collect();
+        let mut level: Vec<&PageTitle> = roots.iter().collect();
+
+        for _ in 0..max_depth {
+            let mut next_level = Vec::new();
+            for category in level {
+                for id in self.0.get(category).into_iter().flatten() {
+                    let subcat = match index.get(id) {
+                        Some(page) if page.ns == CATEGORY_NS => page,
+                        _ => continue,
+                    };
+                    // Categories already in the tree are skipped, which also breaks cycles
+                    if tree.insert((CATEGORY_NS, subcat.title.clone())) {
+                        next_level.push(&subcat.title);
+                    }
+                }
+            }
+            if next_level.is_empty() {
+                break;
+            }
+            level = next_level;
+        }
+
+        tree
+    }
+}
+
+struct SubcategoryCollector;
+
+impl<'r> Aggregator<CategoryRow<'r>> for SubcategoryCollector {
+    type Output = Subcategories;
+
+    fn add(&self, output: &mut Subcategories, row: CategoryRow<'r>) {
+        if row.member_type == "subcat" {
+            output
+                .0
+                .entry(PageTitle(row.title.to_string()))
+                .or_default()
+                .push(row.from);
+        }
+    }
+
+    fn merge(&self, output: &mut Subcategories, partial: Subcategories) {
+        for (category, subcats) in partial.0 {
+            output.0.entry(category).or_default().extend(subcats);
+        }
+    }
+}
+
+pub fn collect_subcategories<T>(source: T, buffer_size: usize) -> Result<Subcategories>
+where
+    T: Read + Send,
+{
+    sql_dump::process(
+        source,
+        &CategorylinksParser::new()?,
+        &SubcategoryCollector,
+        "Extracting ‘categorylinks’ table data (subcategories)",
+        buffer_size,
+    )
+}
+
+// Collect the members (of any type) of the categories within each tree, by page ID
+struct MemberCollector<'a> {
+    trees: &'a [CategoryTree],
+}
+
+impl<'r> Aggregator<CategoryRow<'r>> for MemberCollector<'_> {
+    type Output = Vec<AHashSet<PageId>>;
+
+    fn add(&self, output: &mut Self::Output, row: CategoryRow<'r>) {
+        output.resize_with(self.trees.len(), AHashSet::new);
+        for (members, tree) in output.iter_mut().zip(self.trees) {
+            if target_query(tree, CATEGORY_NS, row.title) {
+                members.insert(row.from);
+            }
+        }
+    }
+
+    fn merge(&self, output: &mut Self::Output, partial: Self::Output) {
+        output.resize_with(self.trees.len(), AHashSet::new);
+        for (members, new_members) in output.iter_mut().zip(partial) {
+            members.extend(new_members);
+        }
+    }
+}
+
+pub fn collect_members<T>(
+    source: T,
+    trees: &[CategoryTree],
+    buffer_size: usize,
+) -> Result<Vec<AHashSet<PageId>>>
+where
+    T: Read + Send,
+{
+    let mut members = sql_dump::process(
+        source,
+        &CategorylinksParser::new()?,
+        &MemberCollector { trees },
+        "Extracting ‘categorylinks’ table data (members)",
+        buffer_size,
+    )?;
+
+    // Trees without any members are otherwise missing
+    members.resize_with(trees.len(), AHashSet::new);
+    Ok(members)
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Rollup {
+    // Sum of the link counts of all member pages
+    pub links: LinkCount,
+    pub pages: u32,
+}
+
+pub type CategoryRollups = AHashMap<(PageNs, PageTitle), Rollup>;
+
+// Link counts of member pages within the given namespaces (as found in the index), other than
+// redirects
+struct MemberLinks<'a> {
+    // Link counts by page ID, as categorylinks rows refer to members by ID
+    link_counts: AHashMap<PageId, LinkCount>,
+    index: &'a PageIndex,
+    namespaces: &'a [PageNs],
+}
+
+impl<'a> MemberLinks<'a> {
+    fn new(pagelinks: &PageLinks, index: &'a PageIndex, namespaces: &'a [PageNs]) -> Self {
+        let link_counts = index
+            .iter()
+            .filter(|(_, page)| namespaces.contains(&page.ns))
+            .filter_map(|(id, page)| {
+                let links = pagelinks.get(&(page.ns, page.title.clone()))?;
+                Some((*id, *links))
+            })
+            .collect();
+        MemberLinks {
+            link_counts,
+            index,
+            namespaces,
+        }
+    }
+
+    // Link count of the given page, or none if it isn't counted as a member
+    fn get(&self, id: &PageId) -> Option<LinkCount> {
+        match self.index.get(id) {
+            Some(page) if !page.is_redirect && self.namespaces.contains(&page.ns) => {
+                Some(self.link_counts.get(id).copied().unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+}
+
+// Sum up the link counts of the members of each category
+struct RollupCollector<'a> {
+    members: &'a MemberLinks<'a>,
+}
+
+impl<'r> Aggregator<CategoryRow<'r>> for RollupCollector<'_> {
+    type Output = CategoryRollups;
+
+    fn add(&self, output: &mut CategoryRollups, row: CategoryRow<'r>) {
+        let links = match self.members.get(&row.from) {
+            Some(links) => links,
+            None => return,
+        };
+        let rollup = match pl_query(output, CATEGORY_NS, row.title) {
+            Some(rollup) => rollup,
+            None => output
+                .entry((CATEGORY_NS, PageTitle(row.title.to_string())))
+                .or_default(),
+        };
+        rollup.links += links;
+        rollup.pages += 1;
+    }
+
+    fn merge(&self, output: &mut CategoryRollups, partial: CategoryRollups) {
+        for (category, new_rollup) in partial {
+            let rollup = output.entry(category).or_default();
+            rollup.links += new_rollup.links;
+            rollup.pages += new_rollup.pages;
+        }
+    }
+}
+
+/*
+Sum up the incoming links of the member pages (within the given namespaces, other than redirects)
+of each category, without those of its subcategories.
+*/
+pub fn roll_up<T>(
+    source: T,
+    pagelinks: &PageLinks,
+    index: &PageIndex,
+    namespaces: &[PageNs],
+    buffer_size: usize,
+) -> Result<CategoryRollups>
+where
+    T: Read + Send,
+{
+    sql_dump::process(
+        source,
+        &CategorylinksParser::new()?,
+        &RollupCollector {
+            members: &MemberLinks::new(pagelinks, index, namespaces),
+        },
+        "Extracting ‘categorylinks’ table data and rolling up link counts",
+        buffer_size,
+    )
+}
+
+/*
+Sum up the incoming links of the member pages (within the given namespaces, other than redirects)
+of each root category's tree, given the members of each tree (see ‘collect_members’). Pages are
+counted once per tree, even if they are found in several of its categories.
+*/
+pub fn roll_up_trees(
+    roots: &[PageTitle],
+    members: &[AHashSet<PageId>],
+    pagelinks: &PageLinks,
+    index: &PageIndex,
+    namespaces: &[PageNs],
+) -> CategoryRollups {
+    let member_links = MemberLinks::new(pagelinks, index, namespaces);
+
+    roots
+        .iter()
+        .zip(members)
+        .map(|(root, members)| {
+            let mut rollup = Rollup::default();
+            for links in members.iter().filter_map(|id| member_links.get(id)) {
+                rollup.links += links;
+                rollup.pages += 1;
+            }
+            ((CATEGORY_NS, root.clone()), rollup)
+        })
+        .collect()
+}
+
+// Add a column with the number of member pages
+pub fn annotate<'a>(columns: &mut Columns<'a>, rollups: &'a CategoryRollups) {
+    columns.annotate("Pages", move |page, _| {
+        Value::Int(rollups.get(page).map_or(0, |r| u64::from(r.pages)))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page_table::PageInfo;
+
+    fn title(s: &str) -> PageTitle {
+        PageTitle(s.to_string())
+    }
+
+    // Category pages by ID, along with the subcategory links between them
+    fn graph(categories: &[(u32, &str)], links: &[(&str, u32)]) -> (Subcategories, PageIndex) {
+        let index = categories
+            .iter()
+            .map(|&(id, name)| {
+                let info = PageInfo {
+                    ns: CATEGORY_NS,
+                    title: title(name),
+                    is_redirect: false,
+                    len: 0,
+                };
+                (PageId(id), info)
+            })
+            .collect();
+
+        let mut subcategories = Subcategories::default();
+        for &(parent, child) in links {
+            subcategories
+                .0
+                .entry(title(parent))
+                .or_default()
+                .push(PageId(child));
+        }
+        (subcategories, index)
+    }
+
+    #[test]
+    fn tree_depth() {
+        let (subcategories, index) = graph(
+            &[(1, "Food"), (2, "Fruit"), (3, "Apples")],
+            &[("Food", 2), ("Fruit", 3)],
+        );
+
+        assert_eq!(subcategories.tree(&[title("Food")], 0, &index).len(), 1);
+        assert_eq!(subcategories.tree(&[title("Food")], 1, &index).len(), 2);
+        let tree = subcategories.tree(&[title("Food")], 5, &index);
+        assert_eq!(tree.len(), 3);
+        assert!(tree.contains(&(CATEGORY_NS, title("Apples"))));
+    }
+
+    #[test]
+    fn tree_self_referential_cycle() {
+        let (subcategories, index) = graph(&[(1, "Loop")], &[("Loop", 1)]);
+
+        let tree = subcategories.tree(&[title("Loop")], u32::MAX, &index);
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn tree_cycle_through_subcategories() {
+        let (subcategories, index) = graph(
+            &[(1, "A"), (2, "B"), (3, "C")],
+            &[("A", 2), ("B", 3), ("C", 1)],
+        );
+
+        let tree = subcategories.tree(&[title("B")], u32::MAX, &index);
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn tree_skips_subcategories_outside_index() {
+        let (subcategories, index) = graph(&[(1, "A")], &[("A", 2)]);
+
+        assert_eq!(subcategories.tree(&[title("A")], 1, &index).len(), 1);
+    }
+
+    #[test]
+    fn roll_up_counts_articles_once() {
+        let dump = "INSERT INTO `categorylinks` VALUES \
+            (2,'Fruit','APPLES','2020-01-01 00:00:00','','uppercase','subcat'),\
+            (10,'Fruit','APPLE','2020-01-01 00:00:00','','uppercase','page'),\
+            (11,'Fruit','PEAR','2020-01-01 00:00:00','','uppercase','page'),\
+            (10,'Apples','APPLE','2020-01-01 00:00:00','','uppercase','page'),\
+            (12,'Apples','MALUS','2020-01-01 00:00:00','','uppercase','page');\n";
+        let (_, mut index) = graph(&[(1, "Fruit"), (2, "Apples")], &[]);
+        let mut pagelinks = PageLinks::new();
+        for &(id, name, is_redirect, links) in &[
+            (10, "Apple", false, 3),
+            (11, "Pear", false, 1),
+            (12, "Malus", true, 5),
+        ] {
+            let info = PageInfo {
+                ns: PageNs(0),
+                title: title(name),
+                is_redirect,
+                len: 0,
+            };
+            index.insert(PageId(id), info);
+            pagelinks.insert((PageNs(0), title(name)), LinkCount::new(links, 0));
+        }
+        let namespaces = &[PageNs(0)];
+
+        let rollups = roll_up(dump.as_bytes(), &pagelinks, &index, namespaces, 1024).unwrap();
+        assert_eq!(rollups.len(), 2);
+        let apples = rollups[&(CATEGORY_NS, title("Apples"))];
+        assert_eq!((apples.pages, apples.links.total()), (1, 3));
+
+        let roots = [title("Fruit")];
+        let subcategories = collect_subcategories(dump.as_bytes(), 1024).unwrap();
+        let trees = [subcategories.tree(&roots, 1, &index)];
+        let members = collect_members(dump.as_bytes(), &trees, 1024).unwrap();
+        let rollups = roll_up_trees(&roots, &members, &pagelinks, &index, namespaces);
+        assert_eq!(rollups.len(), 1);
+        let fruit = rollups[&(CATEGORY_NS, title("Fruit"))];
+        assert_eq!((fruit.pages, fruit.links.total()), (2, 4));
+    }
+}
@@ -0,0 +1,223 @@
+/*
+Process SQL dumps for the MediaWiki “categorylinks” table, i.e. count members per category, split by
+member type (page, subcategory, file) and by the namespace of the member page.
+*/
+use crate::{
+    link_count::LinkCount,
+    page_table::PageIndex,
+    pagelinks_table::{pl_query, rd_query, target_query},
+    redirect_table::RedirectMap,
+    results::{Columns, Value},
+    sql_dump::{self, Aggregator, RowParser},
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+const CATEGORY_NS: PageNs = PageNs(14);
+
+#[derive(Default)]
+pub struct CategorySize {
+    // Members of the category itself (direct) or of a redirect to it (indirect)
+    pub members: LinkCount,
+    pub pages: u32,
+    pub subcats: u32,
+    pub files: u32,
+    pub namespaces: AHashMap<PageNs, u32>,
+}
+
+impl CategorySize {
+    fn add_member(&mut self, ns: PageNs, member_type: &str, via_redirect: bool) {
+        if via_redirect {
+            self.members.indirect += 1;
+        } else {
+            self.members.direct += 1;
+        }
+        match member_type {
+            "subcat" => self.subcats += 1,
+            "file" => self.files += 1,
+            _ => self.pages += 1,
+        }
+        *self.namespaces.entry(ns).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: CategorySize) {
+        self.members += other.members;
+        self.pages += other.pages;
+        self.subcats += other.subcats;
+        self.files += other.files;
+        for (ns, count) in other.namespaces {
+            *self.namespaces.entry(ns).or_default() += count;
+        }
+    }
+}
+
+pub type CategorySizes = AHashMap<(PageNs, PageTitle), CategorySize>;
+
+pub struct CategoryRow<'a> {
+    pub from: PageId,
+    pub title: &'a str,
+    // One of ‘page’, ‘subcat’ or ‘file’
+    pub member_type: &'a str,
+}
+
+pub struct CategorylinksParser {
+    regex: Regex,
+}
+
+impl CategorylinksParser {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            regex: build_categorylinks_regex()?,
+        })
+    }
+}
+
+impl RowParser for CategorylinksParser {
+    type Row<'a> = CategoryRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<CategoryRow<'a>> {
+        Some(CategoryRow {
+            from: PageId(cap[1].parse::<u32>().unwrap()),
+            title: cap.get(2).unwrap().as_str(),
+            member_type: cap.get(3).unwrap().as_str(),
+        })
+    }
+}
+
+/*
+Count members of categories, with those of category redirects added to their target (as indirect).
+Only member pages within the given (‘from’) namespaces are counted, or of any namespace if none are
+given. The index may hold pages of other namespaces too, so it only serves to look up the member.
+*/
+struct MemberCounter<'a> {
+    redirects: &'a RedirectMap,
+    index: &'a PageIndex,
+    namespaces: &'a [PageNs],
+    targets: Option<&'a AHashSet<(PageNs, PageTitle)>>,
+}
+
+impl<'r> Aggregator<CategoryRow<'r>> for MemberCounter<'_> {
+    type Output = CategorySizes;
+
+    fn add(&self, output: &mut CategorySizes, row: CategoryRow<'r>) {
+        let member_ns = match self.index.get(&row.from) {
+            Some(member) if self.namespaces.is_empty() || self.namespaces.contains(&member.ns) => {
+                member.ns
+            }
+            _ => return,
+        };
+
+        let (title, via_redirect) = match rd_query(self.redirects, CATEGORY_NS, row.title) {
+            Some(re_title) => (re_title.0.as_str(), true),
+            None => {
+                if let Some(targets) = self.targets {
+                    if !target_query(targets, CATEGORY_NS, row.title) {
+                        return;
+                    }
+                }
+                (row.title, false)
+            }
+        };
+
+        if let Some(size) = pl_query(output, CATEGORY_NS, title) {
+            size.add_member(member_ns, row.member_type, via_redirect);
+        } else {
+            let mut size = CategorySize::default();
+            size.add_member(member_ns, row.member_type, via_redirect);
+            output.insert((CATEGORY_NS, PageTitle(title.to_string())), size);
+        }
+    }
+
+    fn merge(&self, output: &mut CategorySizes, partial: CategorySizes) {
+        for (category, new_size) in partial {
+            output.entry(category).or_default().merge(new_size);
+        }
+    }
+}
+
+pub fn count_members<T>(
+    source: T,
+    redirects: RedirectMap,
+    index: &PageIndex,
+    namespaces: &[PageNs],
+    targets: Option<&AHashSet<(PageNs, PageTitle)>>,
+    buffer_size: usize,
+) -> Result<CategorySizes>
+where
+    T: Read + Send,
+{
+    let parser = CategorylinksParser::new()?;
+    let counter = MemberCounter {
+        redirects: &redirects,
+        index,
+        namespaces,
+        targets,
+    };
+
+    sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘categorylinks’ table data and counting members",
+        buffer_size,
+    )
+}
+
+// Add columns for the member counts per type, and per namespace (e.g. ‘0: 120, 14: 3’)
+pub fn annotate<'a>(columns: &mut Columns<'a>, categories: &'a CategorySizes) {
+    let size = move |page: &(PageNs, PageTitle)| categories.get(page);
+
+    columns.annotate("Pages", move |page, _| {
+        Value::Int(size(page).map_or(0, |s| u64::from(s.pages)))
+    });
+    columns.annotate("Subcats", move |page, _| {
+        Value::Int(size(page).map_or(0, |s| u64::from(s.subcats)))
+    });
+    columns.annotate("Files", move |page, _| {
+        Value::Int(size(page).map_or(0, |s| u64::from(s.files)))
+    });
+    columns.annotate("Namespaces", move |page, _| match size(page) {
+        Some(size) => {
+            let mut namespaces: Vec<_> = size.namespaces.iter().collect();
+            namespaces.sort_unstable_by_key(|(ns, _)| ns.0);
+            let namespaces: Vec<_> = namespaces
+                .into_iter()
+                .map(|(ns, count)| format!("{}: {}", ns, count))
+                .collect();
+            Value::Text(namespaces.join(", "))
+        }
+        None => Value::Empty,
+    });
+}
+
+/*
+Regex Pattern: \((\d+),'((?:[^']|\\'){1,255}?)','(?:[^'\\]|\\.)*','[^']*','(?:[^'\\]|\\.)*','[^']*','(page|subcat|file)'\)
+
+(\d+) : match and capture the ‘cl_from’ field.
+
+'((?:[^']|\\'){1,255}?)' : match and capture ‘cl_to’, the title of the category (without namespace
+prefix).
+
+'(?:[^'\\]|\\.)*' : match the binary ‘cl_sortkey’ field, which may contain any escaped character.
+
+'[^']*' : match the ‘cl_timestamp’ field.
+
+'(?:[^'\\]|\\.)*' : match the ‘cl_sortkey_prefix’ field.
+
+'[^']*' : match the ‘cl_collation’ field.
+
+'(page|subcat|file)' : match and capture the ‘cl_type’ field.
+*/
+fn build_categorylinks_regex() -> Result<Regex> {
+    let pattern = r"\((\d+),'((?:[^']|\\'){1,255}?)','(?:[^'\\]|\\.)*','[^']*','(?:[^'\\]|\\.)*','[^']*','(page|subcat|file)'\)";
+    Regex::new(pattern).context("Building categorylinks regex")
+}
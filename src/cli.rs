@@ -1,10 +1,10 @@
 /*
 Parsing CLI arguments
 */
-use crate::util::{ExportFormat, PageNs};
+use crate::util::{self, ExportFormat, Mode, PageNs, PageTitle, RunInfo};
 
-use anyhow::Result;
-use clap::{App, Arg};
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches};
 
 use std::convert::TryFrom;
 use std::path::PathBuf;
@@ -13,13 +13,80 @@ use std::str::FromStr;
 pub struct CliParams {
     pub page_file: PathBuf,
     pub redirect_file: PathBuf,
-    pub pagelinks_file: PathBuf,
+    pub pagelinks_file: Option<PathBuf>,
+    pub templatelinks_file: Option<PathBuf>,
+    pub linktarget_file: Option<PathBuf>,
+    pub categorylinks_file: Option<PathBuf>,
+    pub imagelinks_file: Option<PathBuf>,
+    pub externallinks_file: Option<PathBuf>,
+    pub langlinks_file: Option<PathBuf>,
+    pub iwlinks_file: Option<PathBuf>,
+    pub page_props_file: Option<PathBuf>,
+    pub page_restrictions_file: Option<PathBuf>,
     pub output_file: PathBuf,
     pub namespaces_from: Vec<PageNs>,
     pub namespaces_to: Vec<PageNs>,
     pub buf_size_mib: usize,
     pub cutoff_threshold: u32,
     pub export_format: ExportFormat,
+    pub targets_file: Option<PathBuf>,
+    pub target_titles: Vec<String>,
+    pub mode: Mode,
+    pub mark_missing: bool,
+    pub orphans_via_redirects: bool,
+    pub exclude_self_links: bool,
+    pub exclude_redirect_sources: bool,
+    pub stats_file: Option<PathBuf>,
+    pub url_prefix_depth: Option<usize>,
+    pub missing_language: Option<String>,
+    pub interwiki_prefixes: Vec<String>,
+    pub exclude_disambiguation: bool,
+    pub missing_wikidata_item: bool,
+    pub target_categories: Vec<PageTitle>,
+    pub source_categories: Vec<PageTitle>,
+    pub category_depth: u32,
+    pub unprotected: bool,
+    pub max_bytes: u32,
+    pub min_links: u32,
+    pub by_redirect_category: bool,
+    pub unsorted: bool,
+    pub wiki_url: String,
+}
+
+impl CliParams {
+    pub fn run_info(&self) -> RunInfo {
+        let inputs = [
+            ("page", Some(&self.page_file)),
+            ("redirect", Some(&self.redirect_file)),
+            ("pagelinks", self.pagelinks_file.as_ref()),
+            ("templatelinks", self.templatelinks_file.as_ref()),
+            ("linktarget", self.linktarget_file.as_ref()),
+            ("categorylinks", self.categorylinks_file.as_ref()),
+            ("imagelinks", self.imagelinks_file.as_ref()),
+            ("externallinks", self.externallinks_file.as_ref()),
+            ("langlinks", self.langlinks_file.as_ref()),
+            ("iwlinks", self.iwlinks_file.as_ref()),
+            ("page_props", self.page_props_file.as_ref()),
+            ("page_restrictions", self.page_restrictions_file.as_ref()),
+            ("targets", self.targets_file.as_ref()),
+        ];
+
+        RunInfo {
+            mode: self.mode,
+            inputs: inputs
+                .iter()
+                .filter_map(|&(table, path)| Some((table, path?.clone())))
+                .collect(),
+            namespaces_from: self.namespaces_from.clone(),
+            namespaces_to: self.namespaces_to.clone(),
+            // Stubs are filtered by their own minimum instead of the cutoff
+            cutoff: match self.mode {
+                Mode::Stubs => self.min_links,
+                _ => self.cutoff_threshold,
+            },
+            wiki_url: self.wiki_url.clone(),
+        }
+    }
 }
 
 pub fn init_cli_app() -> Result<CliParams> {
@@ -32,6 +99,11 @@ pub fn init_cli_app() -> Result<CliParams> {
             1. The page-table SQL dump (…page.sql.gz)\n\
             2. The redirect-table SQL dump (…redirect.sql.gz)\n\
             3. The pagelinks-table SQL dump (…pagelinks.sql.gz)\n\n\
+            In ‘templates’, ‘categories’, ‘files’, ‘domains’ and ‘interwiki’ mode, the \
+            templatelinks-, categorylinks-, imagelinks-, externallinks- and iwlinks-table SQL dumps \
+            respectively take the place of the pagelinks table. Newer \
+            templatelinks and imagelinks dumps also require the linktarget-table SQL dump \
+            (…linktarget.sql.gz).\n\n\
             For the English Wikipedia, you can get these at https://dumps.wikimedia.org/enwiki/",
         )
         // Page file
@@ -61,8 +133,47 @@ pub fn init_cli_app() -> Result<CliParams> {
                 .long("pagelinks-file")
                 .value_name("PATH")
                 .help("Path to ‘…pagelinks.sql(.gz)’")
-                .takes_value(true)
-                .required(true),
+                .takes_value(true),
+        )
+        // Templatelinks file
+        .arg(
+            Arg::with_name("file-templatelinks")
+                .long("templatelinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…templatelinks.sql(.gz)’")
+                .takes_value(true),
+        )
+        // Linktarget file
+        .arg(
+            Arg::with_name("file-linktarget")
+                .long("linktarget-file")
+                .value_name("PATH")
+                .help("Path to ‘…linktarget.sql(.gz)’, for link tables in the newer schema")
+                .takes_value(true),
+        )
+        // Categorylinks file
+        .arg(
+            Arg::with_name("file-categorylinks")
+                .long("categorylinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…categorylinks.sql(.gz)’")
+                .takes_value(true),
+        )
+        // Imagelinks file
+        .arg(
+            Arg::with_name("file-imagelinks")
+                .long("imagelinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…imagelinks.sql(.gz)’")
+                .takes_value(true),
+        )
+        // Externallinks file
+        .arg(
+            Arg::with_name("file-externallinks")
+                .long("externallinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…externallinks.sql(.gz)’")
+                .takes_value(true),
         )
         // Output file
         .arg(
@@ -138,13 +249,347 @@ pub fn init_cli_app() -> Result<CliParams> {
                 .long("export-as")
                 .value_name("FORMAT")
                 .help("Format to output results as")
-                .long_help("Supported formats are: text (plain), wikitext, markdown (gfm)")
+                .long_help(
+                    "Supported formats are: text (plain), wiki (wikitext), markdown (gfm), json \
+                    (with run parameters and typed fields), csv, tsv, ndjson (one JSON object per \
+                    line), html (standalone report with sortable table)",
+                )
                 .default_value("text")
                 .takes_value(true)
-                .validator(|f| {
-                    ExportFormat::try_from(f.as_str())
+                .validator(|f| ExportFormat::try_from(f.as_str()).map(|_| ())),
+        )
+        // Wiki base URL
+        .arg(
+            Arg::with_name("wiki-url")
+                .long("wiki-url")
+                .value_name("URL")
+                .help("Base URL of page links in HTML output")
+                .long_help(
+                    "Base URL of page links in HTML output, to which the title is appended, e.g. \
+                    ‘https://de.wikipedia.org/wiki/’ for the German Wikipedia",
+                )
+                .default_value("https://en.wikipedia.org/wiki/")
+                .takes_value(true),
+        )
+        // Skip sorting
+        .arg(
+            Arg::with_name("unsorted")
+                .long("unsorted")
+                .help("Output results in no particular order, skipping the sort (ndjson only)")
+                .long_help(
+                    "Output results in no particular order, as they are found in the counted \
+                    links, rather than sorted by link count. Rows are written out as they are \
+                    produced, which saves the time and memory of collecting and sorting very large \
+                    results, e.g. with --cutoff 0. Only supported with ‘--export-as ndjson’.",
+                ),
+        )
+        // Targets file
+        .arg(
+            Arg::with_name("file-targets")
+                .long("targets")
+                .value_name("PATH")
+                .help("Count links only to the pages listed in this file")
+                .long_help(
+                    "Count links only to the pages listed in this file, one per line, given as \
+                    title (e.g. ‘Talk:Some page’) or page ID (e.g. ‘id:12345’). Every listed page \
+                    is output, regardless of the cutoff threshold.",
+                )
+                .takes_value(true),
+        )
+        // Target titles
+        .arg(
+            Arg::with_name("target")
+                .long("target")
+                .value_name("TITLE")
+                .help("Count links only to this page (may be repeated)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        // Target categories
+        .arg(
+            Arg::with_name("target-category")
+                .long("target-category")
+                .value_name("TITLE")
+                .help("Count links only to pages in this category tree (may be repeated)")
+                .long_help(
+                    "Count links only to pages in this category (e.g. ‘Category:Physics’), or in \
+                    its subcategories down to --category-depth. May be repeated. Requires \
+                    --categorylinks-file. In ‘category-rollup’ mode, only these categories are \
+                    ranked, each with its tree instead (required with --category-depth).",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("file-categorylinks"),
+        )
+        // Source categories
+        .arg(
+            Arg::with_name("source-category")
+                .long("source-category")
+                .value_name("TITLE")
+                .help("Count only links from pages in this category tree (may be repeated)")
+                .long_help(
+                    "Count only links originating from pages in this category (e.g. \
+                    ‘Category:Physics’), or in its subcategories down to --category-depth. May be \
+                    repeated. Requires --categorylinks-file.",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("file-categorylinks"),
+        )
+        // Category depth
+        .arg(
+            Arg::with_name("category-depth")
+                .long("category-depth")
+                .value_name("N")
+                .help("Levels of subcategories to include in category trees")
+                .long_help(
+                    "Levels of subcategories to include in category trees, for \
+                    --target-category, --source-category and in ‘category-rollup’ mode, where \
+                    the total of each --target-category then covers its tree (counting every page \
+                    once).",
+                )
+                .default_value("0")
+                .takes_value(true)
+                .validator(|n| {
+                    n.parse::<u32>()
                         .map(|_| ())
-                        .map_err(|e| e)
+                        .map_err(|_| "must be a positive number".to_string())
+                }),
+        )
+        // Split links via redirects by redirect category
+        .arg(
+            Arg::with_name("by-redirect-category")
+                .long("by-redirect-category")
+                .help("Add a column splitting links via redirects by redirect category")
+                .long_help(
+                    "Add a column splitting links via redirects by the categories of the \
+                    redirects, e.g. ‘Redirects from misspellings: 120, Redirects from moves: 3’. \
+                    Requires --categorylinks-file.",
+                )
+                .requires("file-categorylinks"),
+        )
+        // Maximum page size
+        .arg(
+            Arg::with_name("max-bytes")
+                .long("max-bytes")
+                .value_name("BYTES")
+                .help("In ‘stubs’ mode, output only pages of at most this size")
+                .default_value("3000")
+                .takes_value(true)
+                .validator(|n| {
+                    n.parse::<u32>()
+                        .map(|_| ())
+                        .map_err(|_| "must be a positive number".to_string())
+                }),
+        )
+        // Minimum link count of stubs
+        .arg(
+            Arg::with_name("min-links")
+                .long("min-links")
+                .value_name("LINKS")
+                .help("In ‘stubs’ mode, output only pages with at least this many incoming links")
+                .default_value("10")
+                .takes_value(true)
+                .validator(|n| {
+                    n.parse::<u32>()
+                        .map(|_| ())
+                        .map_err(|_| "must be a positive number".to_string())
+                }),
+        )
+        // Mode
+        .arg(
+            Arg::with_name("mode")
+                .short("m")
+                .long("mode")
+                .value_name("MODE")
+                .help("What to rank pages by")
+                .long_help(
+                    "Supported modes are: most-linked (pages by incoming links), wanted \
+                    (non-existent pages by incoming links), outgoing (pages by distinct link \
+                    targets, split into links to regular pages and to redirects), templates \
+                    (templates by transclusions, ‘to’ namespace defaults to 10), categories (categories by \
+                    members, split by member type and namespace; ‘to’ namespace is always 14, \
+                    ‘from’ defaults to all namespaces), files (files by pages using them; ‘to’ \
+                    namespace is always 6), domains (external domains by linking pages and link \
+                    rows), interwiki (interwiki titles, e.g. ‘wikt:example’, by incoming links), \
+                    disambiguation (disambiguation pages by incoming links, see \
+                    --page-props-file), category-rollup (categories by the sum of incoming links \
+                    to their member articles, see --target-category), stubs (short pages by \
+                    incoming links per kilobyte, see --max-bytes and --min-links), \
+                    redirect-categories (redirect categories, e.g. ‘Redirects from \
+                    misspellings’, by links via their redirects), orphans (pages without \
+                    incoming links), dead-ends (pages without links into any namespace, \
+                    regardless of ‘to’ namespaces), what-links-here \
+                    (every link to the given targets, see --target and --targets)",
+                )
+                .default_value("most-linked")
+                .takes_value(true)
+                .validator(|m| Mode::try_from(m.as_str()).map(|_| ())),
+        )
+        // Mark missing pages
+        .arg(
+            Arg::with_name("mark-missing")
+                .long("mark-missing")
+                .help("Add a column marking pages that don't exist")
+                .long_help(
+                    "Add a column marking pages that don't exist. In ‘files’ mode, this marks \
+                    files without a local file page, e.g. those hosted on Wikimedia Commons.",
+                ),
+        )
+        // Orphans via redirects
+        .arg(
+            Arg::with_name("orphans-via-redirects")
+                .long("orphans-via-redirects")
+                .help("Don't report pages as orphans that are linked to via redirects"),
+        )
+        // Exclude self-links
+        .arg(
+            Arg::with_name("exclude-self-links")
+                .long("exclude-self-links")
+                .help("Don't count links from a page to itself, including via a redirect"),
+        )
+        // Exclude links from redirects
+        .arg(
+            Arg::with_name("exclude-redirect-sources")
+                .long("exclude-redirect-sources")
+                .help("Don't count links originating from redirect pages"),
+        )
+        // Statistics file
+        .arg(
+            Arg::with_name("file-stats")
+                .long("stats")
+                .value_name("PATH")
+                .help("Write summary statistics of the link counts to this file")
+                .long_help(
+                    "Write summary statistics of the link counts (rows scanned, share of links \
+                    via redirects, in-degree distribution, etc.) to this file; as JSON if the \
+                    file name ends in ‘.json’, otherwise as plain text. Only supported in modes \
+                    counting incoming pagelinks (e.g. most-linked and wanted).",
+                )
+                .takes_value(true),
+        )
+        // Iwlinks file
+        .arg(
+            Arg::with_name("file-iwlinks")
+                .long("iwlinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…iwlinks.sql(.gz)’")
+                .takes_value(true),
+        )
+        // Langlinks file
+        .arg(
+            Arg::with_name("file-langlinks")
+                .long("langlinks-file")
+                .value_name("PATH")
+                .help("Path to ‘…langlinks.sql(.gz)’, to add a column with the number of \
+                    language editions each page is linked to")
+                .takes_value(true),
+        )
+        // Missing language
+        .arg(
+            Arg::with_name("missing-language")
+                .long("missing-language")
+                .value_name("CODE")
+                .help("Output only existing pages without a link to this language edition")
+                .long_help(
+                    "Output only existing pages without an interlanguage link to this language \
+                    edition (e.g. ‘de’ or ‘zh-yue’), such as the most linked untranslated articles. \
+                    Requires --langlinks-file.",
+                )
+                .takes_value(true)
+                .requires("file-langlinks")
+                .validator(|code| {
+                    if !code.is_empty()
+                        && code
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                    {
+                        Ok(())
+                    } else {
+                        Err("must be a language code, e.g. ‘de’".to_string())
+                    }
+                }),
+        )
+        // Page props file
+        .arg(
+            Arg::with_name("file-page-props")
+                .long("page-props-file")
+                .value_name("PATH")
+                .help(
+                    "Path to ‘…page_props.sql(.gz)’, to add columns marking disambiguation pages \
+                    and giving the Wikidata item of each page",
+                )
+                .takes_value(true),
+        )
+        // Exclude disambiguation pages
+        .arg(
+            Arg::with_name("exclude-disambiguation")
+                .long("exclude-disambiguation")
+                .help("Don't output disambiguation pages (requires --page-props-file)")
+                .requires("file-page-props"),
+        )
+        // Page restrictions file
+        .arg(
+            Arg::with_name("file-page-restrictions")
+                .long("page-restrictions-file")
+                .value_name("PATH")
+                .help(
+                    "Path to ‘…page_restrictions.sql(.gz)’, to add columns with the edit and move \
+                    protection of each page (ignoring protection which has already expired)",
+                )
+                .takes_value(true),
+        )
+        // Unprotected pages
+        .arg(
+            Arg::with_name("unprotected")
+                .long("unprotected")
+                .help("Output only existing pages without edit protection")
+                .long_help(
+                    "Output only existing pages without edit protection, such as the most linked \
+                    pages or templates that are open to vandalism. Requires \
+                    --page-restrictions-file.",
+                )
+                .requires("file-page-restrictions"),
+        )
+        // Missing Wikidata item
+        .arg(
+            Arg::with_name("missing-wikidata-item")
+                .long("missing-wikidata-item")
+                .help("Output only existing pages without a Wikidata item")
+                .long_help(
+                    "Output only existing pages that aren't connected to a Wikidata item, i.e. \
+                    lack the ‘wikibase_item’ page property. Requires --page-props-file.",
+                )
+                .requires("file-page-props"),
+        )
+        // Interwiki prefixes
+        .arg(
+            Arg::with_name("interwiki-prefixes")
+                .long("interwiki-prefixes")
+                .value_name("prefix,prefix,…")
+                .help("In ‘interwiki’ mode, count only links with these prefixes (e.g. wikt)")
+                .takes_value(true)
+                .use_delimiter(true),
+        )
+        // URL prefix depth
+        .arg(
+            Arg::with_name("url-prefix-depth")
+                .long("url-prefix-depth")
+                .value_name("N")
+                .help("In ‘domains’ mode, count per host name and first N path segments")
+                .long_help(
+                    "In ‘domains’ mode, count per URL prefix, made up of the full host name and \
+                    the first N path segments (e.g. ‘news.example.org/2020’ for N = 1), instead of \
+                    per registrable domain (e.g. ‘example.org’).",
+                )
+                .takes_value(true)
+                .validator(|n| {
+                    n.parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|_| "must be a positive number".to_string())
                 }),
         )
         .get_matches();
@@ -152,15 +597,26 @@ pub fn init_cli_app() -> Result<CliParams> {
     // Conversion
     let page_file = PathBuf::from_str(matches.value_of("file-page").unwrap())?;
     let redirect_file = PathBuf::from_str(matches.value_of("file-redirect").unwrap())?;
-    let pagelinks_file = PathBuf::from_str(matches.value_of("file-pagelinks").unwrap())?;
+    let pagelinks_file = optional_path(&matches, "file-pagelinks")?;
+    let templatelinks_file = optional_path(&matches, "file-templatelinks")?;
+    let linktarget_file = optional_path(&matches, "file-linktarget")?;
+    let categorylinks_file = optional_path(&matches, "file-categorylinks")?;
+    let imagelinks_file = optional_path(&matches, "file-imagelinks")?;
+    let externallinks_file = optional_path(&matches, "file-externallinks")?;
+    let langlinks_file = optional_path(&matches, "file-langlinks")?;
+    let iwlinks_file = optional_path(&matches, "file-iwlinks")?;
+    let page_props_file = optional_path(&matches, "file-page-props")?;
+    let page_restrictions_file = optional_path(&matches, "file-page-restrictions")?;
     let output_file = PathBuf::from_str(matches.value_of("file-output").unwrap())?;
+    let targets_file = optional_path(&matches, "file-targets")?;
+    let stats_file = optional_path(&matches, "file-stats")?;
 
-    let namespaces_from = matches
+    let mut namespaces_from = matches
         .values_of("namespaces-from")
         .unwrap()
         .map(|ns| PageNs(ns.parse::<u32>().unwrap()))
         .collect::<Vec<PageNs>>();
-    let namespaces_to = matches
+    let mut namespaces_to = matches
         .values_of("namespaces-to")
         .unwrap()
         .map(|ns| PageNs(ns.parse::<u32>().unwrap()))
@@ -172,18 +628,233 @@ pub fn init_cli_app() -> Result<CliParams> {
         .unwrap()
         .parse::<u32>()?;
     let export_format = ExportFormat::try_from(matches.value_of("export-format").unwrap()).unwrap();
+    let target_titles = match matches.values_of("target") {
+        Some(titles) => titles.map(String::from).collect(),
+        None => Vec::new(),
+    };
+    let mode = Mode::try_from(matches.value_of("mode").unwrap()).unwrap();
+    let target_categories = parse_categories(&matches, "target-category")?;
+    let source_categories = parse_categories(&matches, "source-category")?;
+    let category_depth = matches.value_of("category-depth").unwrap().parse::<u32>()?;
+
+    // Each mode reads its links from a particular table
+    let (links_file, links_arg) = match mode {
+        Mode::Templates => (&templatelinks_file, "--templatelinks-file"),
+        Mode::Categories => (&categorylinks_file, "--categorylinks-file"),
+        Mode::Files => (&imagelinks_file, "--imagelinks-file"),
+        Mode::Domains => (&externallinks_file, "--externallinks-file"),
+        Mode::Interwiki => (&iwlinks_file, "--iwlinks-file"),
+        _ => (&pagelinks_file, "--pagelinks-file"),
+    };
+    if links_file.is_none() {
+        bail!(
+            "Mode ‘{}’ requires {}",
+            matches.value_of("mode").unwrap(),
+            links_arg
+        );
+    }
+    if mode == Mode::Templates && matches.occurrences_of("namespaces-to") == 0 {
+        namespaces_to = vec![PageNs(10)];
+    }
+    // Category members are counted from all namespaces (left empty), unless given
+    if mode == Mode::Categories {
+        if matches.occurrences_of("namespaces-from") == 0 {
+            namespaces_from = Vec::new();
+        }
+        namespaces_to = vec![PageNs(14)];
+    }
+    if mode == Mode::Files {
+        namespaces_to = vec![PageNs(6)];
+    }
+    if mode == Mode::WhatLinksHere && targets_file.is_none() && target_titles.is_empty() {
+        bail!("Mode ‘what-links-here’ requires targets, given via --target or --targets");
+    }
+    if mode == Mode::CategoryRollup && categorylinks_file.is_none() {
+        bail!("Mode ‘category-rollup’ requires --categorylinks-file");
+    }
+    if mode == Mode::RedirectCategories && categorylinks_file.is_none() {
+        bail!("Mode ‘redirect-categories’ requires --categorylinks-file");
+    }
+    let by_redirect_category = matches.is_present("by-redirect-category");
+    if by_redirect_category
+        && !matches!(
+            mode,
+            Mode::MostLinked | Mode::Wanted | Mode::Disambiguation | Mode::Stubs
+        )
+    {
+        bail!(
+            "Mode ‘{}’ doesn't support --by-redirect-category",
+            matches.value_of("mode").unwrap()
+        );
+    }
+    if stats_file.is_some()
+        && matches!(
+            mode,
+            Mode::Outgoing
+                | Mode::DeadEnds
+                | Mode::WhatLinksHere
+                | Mode::Templates
+                | Mode::Categories
+                | Mode::Files
+                | Mode::Domains
+                | Mode::Interwiki
+        )
+    {
+        bail!(
+            "Mode ‘{}’ doesn't support --stats",
+            matches.value_of("mode").unwrap()
+        );
+    }
+    let unsorted = matches.is_present("unsorted");
+    let wiki_url = matches.value_of("wiki-url").unwrap().to_string();
+    if unsorted {
+        if !matches!(export_format, ExportFormat::Ndjson) {
+            bail!("--unsorted is only supported with ‘--export-as ndjson’");
+        }
+        if matches!(
+            mode,
+            Mode::Orphans
+                | Mode::DeadEnds
+                | Mode::WhatLinksHere
+                | Mode::Domains
+                | Mode::Stubs
+                | Mode::RedirectCategories
+        ) {
+            bail!(
+                "Mode ‘{}’ doesn't support --unsorted",
+                matches.value_of("mode").unwrap()
+            );
+        }
+    }
+    if mode == Mode::Disambiguation && page_props_file.is_none() {
+        bail!("Mode ‘disambiguation’ requires --page-props-file");
+    }
+    if !target_categories.is_empty()
+        && !matches!(
+            mode,
+            Mode::MostLinked | Mode::Disambiguation | Mode::CategoryRollup
+        )
+    {
+        bail!(
+            "--target-category is only supported in modes ‘most-linked’, ‘disambiguation’ \
+            and ‘category-rollup’"
+        );
+    }
+    if !source_categories.is_empty() && !matches!(mode, Mode::MostLinked | Mode::Disambiguation) {
+        bail!(
+            "--source-category is only supported in modes ‘most-linked’ and \
+            ‘disambiguation’"
+        );
+    }
+    // Rolling up every category's tree separately would take far too long
+    if mode == Mode::CategoryRollup && category_depth > 0 && target_categories.is_empty() {
+        bail!("Mode ‘category-rollup’ requires --target-category with --category-depth");
+    }
+    // Rows of these modes aren't the linked pages, which targets would select
+    if matches!(mode, Mode::Domains | Mode::Interwiki | Mode::CategoryRollup)
+        && (targets_file.is_some() || !target_titles.is_empty())
+    {
+        bail!(
+            "Mode ‘{}’ doesn't support targets",
+            matches.value_of("mode").unwrap()
+        );
+    }
+    // Rows of these modes aren't pages, so there is nothing to annotate
+    if matches!(mode, Mode::Domains | Mode::Interwiki) {
+        let flags = [
+            ("file-langlinks", "--langlinks-file"),
+            ("file-page-props", "--page-props-file"),
+            ("file-page-restrictions", "--page-restrictions-file"),
+            ("mark-missing", "--mark-missing"),
+        ];
+        if let Some((_, flag)) = flags.iter().find(|(name, _)| matches.is_present(name)) {
+            bail!(
+                "Mode ‘{}’ doesn't support {}",
+                matches.value_of("mode").unwrap(),
+                flag
+            );
+        }
+    }
+    let mark_missing = matches.is_present("mark-missing");
+    let orphans_via_redirects = matches.is_present("orphans-via-redirects");
+    let exclude_self_links = matches.is_present("exclude-self-links");
+    let exclude_redirect_sources = matches.is_present("exclude-redirect-sources");
+    let url_prefix_depth = match matches.value_of("url-prefix-depth") {
+        Some(depth) => Some(depth.parse::<usize>()?),
+        None => None,
+    };
+    let missing_language = matches.value_of("missing-language").map(String::from);
+    let interwiki_prefixes = match matches.values_of("interwiki-prefixes") {
+        Some(prefixes) => prefixes.map(String::from).collect(),
+        None => Vec::new(),
+    };
+    let exclude_disambiguation = matches.is_present("exclude-disambiguation");
+    let missing_wikidata_item = matches.is_present("missing-wikidata-item");
+    let unprotected = matches.is_present("unprotected");
+    let max_bytes = matches.value_of("max-bytes").unwrap().parse::<u32>()?;
+    let min_links = matches.value_of("min-links").unwrap().parse::<u32>()?;
 
     let cli_params = CliParams {
         page_file,
         redirect_file,
         pagelinks_file,
+        templatelinks_file,
+        linktarget_file,
+        categorylinks_file,
+        imagelinks_file,
+        externallinks_file,
+        langlinks_file,
+        iwlinks_file,
+        page_props_file,
+        page_restrictions_file,
         output_file,
         buf_size_mib,
         cutoff_threshold,
         namespaces_from,
         namespaces_to,
         export_format,
+        targets_file,
+        target_titles,
+        mode,
+        mark_missing,
+        orphans_via_redirects,
+        exclude_self_links,
+        exclude_redirect_sources,
+        stats_file,
+        url_prefix_depth,
+        missing_language,
+        interwiki_prefixes,
+        exclude_disambiguation,
+        missing_wikidata_item,
+        target_categories,
+        source_categories,
+        category_depth,
+        unprotected,
+        max_bytes,
+        min_links,
+        by_redirect_category,
+        unsorted,
+        wiki_url,
     };
 
     Ok(cli_params)
 }
+
+// Category titles, given with or without the ‘Category:’ prefix
+fn parse_categories(matches: &ArgMatches, name: &str) -> Result<Vec<PageTitle>> {
+    let mut categories = Vec::new();
+    for input in matches.values_of(name).into_iter().flatten() {
+        match util::parse_title(input) {
+            (PageNs(0), title) | (PageNs(14), title) => categories.push(title),
+            _ => bail!("‘{}’ is not a category", input),
+        }
+    }
+    Ok(categories)
+}
+
+fn optional_path(matches: &ArgMatches, name: &str) -> Result<Option<PathBuf>> {
+    match matches.value_of(name) {
+        Some(path) => Ok(Some(PathBuf::from_str(path)?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,330 @@
+/*
+Process SQL dumps for the MediaWiki “externallinks” table, i.e. count linking pages and link rows per
+domain (or URL prefix). Both the legacy schema (with the full URL in ‘el_to’) and the newer one (with
+‘el_to_domain_index’ and ‘el_to_path’) are supported.
+*/
+use crate::{
+    page_table::PageIndex,
+    results::Value,
+    sql_dump::{self, Aggregator, RowParser},
+    util::{PageId, PageNs},
+};
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+use std::net::IpAddr;
+
+/*
+Common second-level labels under country-code TLDs (e.g. ‘co.uk’, ‘com.au’), under which domains are
+registered one level deeper. This is a rough stand-in for the Public Suffix List.
+*/
+const SECOND_LEVEL_LABELS: &[&str] = &[
+    "ac", "co", "com", "edu", "go", "gob", "gov", "ltd", "mil", "ne", "net", "nic", "or", "org",
+    "plc", "sch",
+];
+
+#[derive(Default)]
+struct DomainCount {
+    pages: AHashSet<PageId>,
+    links: u64,
+}
+
+pub struct ExternalLink<'a> {
+    pub from: PageId,
+    // Lowercase host name
+    pub host: String,
+    pub path: &'a str,
+}
+
+pub struct ExternallinksParser {
+    regex: Regex,
+}
+
+impl RowParser for ExternallinksParser {
+    type Row<'a> = ExternalLink<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<ExternalLink<'a>> {
+        let from = PageId(cap[1].parse::<u32>().unwrap());
+
+        // Only the legacy schema has a fourth (‘el_index_60’) field
+        let (host, path) = if cap.get(4).is_some() {
+            split_url(cap.get(2).unwrap().as_str())?
+        } else {
+            // A NULL ‘el_to_path’ is taken as an empty path
+            (
+                host_from_domain_index(&cap[2])?,
+                cap.get(3).map_or("", |path| path.as_str()),
+            )
+        };
+
+        Some(ExternalLink { from, host, path })
+    }
+}
+
+/*
+Count linking pages (within the given namespaces) and link rows per registrable domain, or, if
+‘prefix_depth’ is given, per host name followed by that many path segments.
+*/
+struct DomainCounter<'a> {
+    index: &'a PageIndex,
+    namespaces: &'a [PageNs],
+    prefix_depth: Option<usize>,
+}
+
+impl<'r> Aggregator<ExternalLink<'r>> for DomainCounter<'_> {
+    type Output = AHashMap<String, DomainCount>;
+
+    fn add(&self, output: &mut Self::Output, link: ExternalLink<'r>) {
+        // The index may also hold pages outside the source namespaces
+        match self.index.get(&link.from) {
+            Some(page) if self.namespaces.contains(&page.ns) => (),
+            _ => return,
+        }
+
+        let key = match self.prefix_depth {
+            Some(depth) => format!("{}{}", link.host, path_prefix(link.path, depth)),
+            None => registrable_domain(&link.host).to_string(),
+        };
+
+        let count = output.entry(key).or_default();
+        count.pages.insert(link.from);
+        count.links += 1;
+    }
+
+    fn merge(&self, output: &mut Self::Output, partial: Self::Output) {
+        for (domain, new_count) in partial {
+            let count = output.entry(domain).or_default();
+            count.pages.extend(new_count.pages);
+            count.links += new_count.links;
+        }
+    }
+}
+
+pub fn count_domains<T>(
+    source: T,
+    index: &PageIndex,
+    namespaces: &[PageNs],
+    prefix_depth: Option<usize>,
+    buffer_size: usize,
+) -> Result<Vec<(String, u64, u64)>>
+where
+    T: Read + Send,
+{
+    let parser = ExternallinksParser {
+        regex: build_externallinks_regex()?,
+    };
+    let counter = DomainCounter {
+        index,
+        namespaces,
+        prefix_depth,
+    };
+
+    let domains = sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘externallinks’ table data and counting domains",
+        buffer_size,
+    )?;
+
+    Ok(domains
+        .into_iter()
+        .map(|(domain, count)| (domain, count.pages.len() as u64, count.links))
+        .collect())
+}
+
+/*
+Reduce dataset to domains with at least ‘cutoff’ linking pages, and sort by linking pages, then link
+rows, in descending order.
+*/
+pub fn sort_domains(domains: Vec<(String, u64, u64)>, cutoff: u32) -> Vec<(String, Vec<Value>)> {
+    let mut domains: Vec<_> = domains
+        .into_iter()
+        .filter(|(_, pages, _)| *pages >= u64::from(cutoff))
+        .collect();
+    domains.sort_unstable_by(|a, b| (b.1, b.2, &a.0).cmp(&(a.1, a.2, &b.0)));
+
+    domains
+        .into_iter()
+        .map(|(domain, pages, links)| (domain, vec![Value::Int(pages), Value::Int(links)]))
+        .collect()
+}
+
+// Lowercase host name and remaining path of a URL, e.g. ‘//example.org/a?b’ or ‘mailto:x@example.org’
+fn split_url(url: &str) -> Option<(String, &str)> {
+    let rest = strip_scheme(url)?;
+    let end = rest.find(&['/', '?', '#'][..]).unwrap_or(rest.len());
+    let authority = &rest[..end];
+
+    // Strip user info and port
+    let host = authority.rsplit('@').next().unwrap();
+    let host = if host.starts_with('[') {
+        &host[..host.find(']').map_or(host.len(), |pos| pos + 1)]
+    } else {
+        host.split(':').next().unwrap()
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some((host.to_lowercase(), &rest[end..]))
+    }
+}
+
+// Host name from its reversed form in ‘el_to_domain_index’, e.g. ‘https://org.example.www.’
+fn host_from_domain_index(domain_index: &str) -> Option<String> {
+    let rest = strip_scheme(domain_index)?;
+    // Strip port, which follows the final dot
+    let rest = match rest.find(".:") {
+        Some(pos) => &rest[..pos],
+        None => rest.trim_end_matches('.'),
+    };
+
+    if rest.is_empty() {
+        None
+    } else {
+        let labels: Vec<&str> = rest.rsplit('.').collect();
+        Some(labels.join(".").to_lowercase())
+    }
+}
+
+// Everything after e.g. ‘https://’, ‘mailto:’ or (protocol-relative) ‘//’
+fn strip_scheme(url: &str) -> Option<&str> {
+    if let Some(rest) = url.strip_prefix("//") {
+        return Some(rest);
+    }
+    let rest = &url[url.find(':')? + 1..];
+    Some(rest.strip_prefix("//").unwrap_or(rest))
+}
+
+fn registrable_domain(host: &str) -> &str {
+    if host.starts_with('[') || host.parse::<IpAddr>().is_ok() {
+        return host;
+    }
+
+    let labels: Vec<&str> = host.rsplit('.').take(3).collect();
+    let keep =
+        if labels.len() == 3 && labels[0].len() == 2 && SECOND_LEVEL_LABELS.contains(&labels[1]) {
+            3
+        } else {
+            2
+        };
+
+    match host.rmatch_indices('.').nth(keep - 1) {
+        Some((pos, _)) => &host[pos + 1..],
+        None => host,
+    }
+}
+
+// First ‘depth’ segments of a path, e.g. ‘/a/b’ of ‘/a/b/c?d’ for a depth of 2
+fn path_prefix(path: &str, depth: usize) -> String {
+    let end = path.find(&['?', '#'][..]).unwrap_or(path.len());
+    path[..end]
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .take(depth)
+        .map(|segment| format!("/{}", segment))
+        .collect()
+}
+
+/*
+Regex Pattern: \(\d+,(\d+),'((?:[^'\\]|\\.)*)',(?:'((?:[^'\\]|\\.)*)'|NULL)(,'(?:[^'\\]|\\.)*')?\)
+
+\d+ : match the ‘el_id’ field.
+
+(\d+) : match and capture the ‘el_from’ field.
+
+'((?:[^'\\]|\\.)*)' : match and capture ‘el_to’ (legacy schema) or ‘el_to_domain_index’ (newer
+schema), which may contain any escaped character.
+
+(?:'((?:[^'\\]|\\.)*)'|NULL) : match and capture ‘el_index’ (legacy schema) or ‘el_to_path’ (newer
+schema). The latter may be NULL.
+
+(,'(?:[^'\\]|\\.)*')? : match and capture ‘el_index_60’, which only exists in the legacy schema.
+*/
+fn build_externallinks_regex() -> Result<Regex> {
+    let pattern =
+        r"\(\d+,(\d+),'((?:[^'\\]|\\.)*)',(?:'((?:[^'\\]|\\.)*)'|NULL)(,'(?:[^'\\]|\\.)*')?\)";
+    Regex::new(pattern).context("Building externallinks regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_url_host_and_path() {
+        assert_eq!(
+            split_url("https://www.Example.org/a/b?c"),
+            Some(("www.example.org".to_string(), "/a/b?c"))
+        );
+        assert_eq!(
+            split_url("//user@example.org:8080"),
+            Some(("example.org".to_string(), ""))
+        );
+        assert_eq!(
+            split_url("mailto:someone@example.org"),
+            Some(("example.org".to_string(), ""))
+        );
+        assert_eq!(
+            split_url("http://[2001:db8::1]:80/x"),
+            Some(("[2001:db8::1]".to_string(), "/x"))
+        );
+        assert_eq!(split_url("https:///path"), None);
+        assert_eq!(split_url("no scheme"), None);
+    }
+
+    #[test]
+    fn host_from_reversed_domain_index() {
+        assert_eq!(
+            host_from_domain_index("https://org.example.www."),
+            Some("www.example.org".to_string())
+        );
+        assert_eq!(
+            host_from_domain_index("http://uk.co.bbc.:8080"),
+            Some("bbc.co.uk".to_string())
+        );
+        assert_eq!(host_from_domain_index("https://."), None);
+    }
+
+    #[test]
+    fn registrable_domain_heuristic() {
+        assert_eq!(registrable_domain("www.example.org"), "example.org");
+        assert_eq!(registrable_domain("example.org"), "example.org");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+        assert_eq!(registrable_domain("news.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(registrable_domain("www.abc.net.au"), "abc.net.au");
+        // Second-level labels only count under two-letter country codes
+        assert_eq!(registrable_domain("www.co.example"), "co.example");
+        assert_eq!(registrable_domain("192.168.0.1"), "192.168.0.1");
+        assert_eq!(registrable_domain("[2001:db8::1]"), "[2001:db8::1]");
+    }
+
+    #[test]
+    fn path_prefix_segments() {
+        assert_eq!(path_prefix("/a/b/c?d", 2), "/a/b");
+        assert_eq!(path_prefix("//a#b", 3), "/a");
+        assert_eq!(path_prefix("", 1), "");
+    }
+
+    #[test]
+    fn regex_accepts_null_path() {
+        let regex = build_externallinks_regex().unwrap();
+        let cap = regex.captures("(1,5,'https://org.example.',NULL)").unwrap();
+        let parser = ExternallinksParser {
+            regex: regex.clone(),
+        };
+        let link = parser.parse(&cap).unwrap();
+        assert_eq!(
+            (link.from, link.host.as_str(), link.path),
+            (PageId(5), "example.org", "")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+/*
+Standalone HTML report: a summary of the run parameters, followed by the results as a table which
+can be sorted (by clicking a column heading) and filtered (by any text within a row). Styles and
+scripts are inlined, so the file can be opened without network access.
+*/
+use crate::{
+    namespace,
+    results::{Key, Results, Value},
+    util::{self, PageNs, RunInfo},
+};
+
+use anyhow::Result;
+
+use std::io::Write;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #202122; }
+h1 { font-size: 1.5em; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.25em 1em; }
+dt { font-weight: bold; }
+dd { margin: 0; }
+input { margin: 1em 0; padding: 0.3em; width: 20em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #a2a9b1; padding: 0.2em 0.5em; }
+th { background: #eaecf0; cursor: pointer; user-select: none; }
+th[data-dir="asc"]::after { content: " ▲"; }
+th[data-dir="desc"]::after { content: " ▼"; }
+td.num { text-align: right; }
+tbody tr:nth-child(even) { background: #f8f9fa; }
+a { color: #36c; text-decoration: none; }
+a:hover { text-decoration: underline; }
+"#;
+
+const SCRIPT: &str = r#"
+const table = document.getElementById("results");
+const body = table.tBodies[0];
+const rows = Array.from(body.rows);
+const headings = Array.from(table.tHead.rows[0].cells);
+
+document.getElementById("filter").addEventListener("input", (event) => {
+    const query = event.target.value.toLowerCase();
+    let shown = 0;
+    for (const row of rows) {
+        row.hidden = !row.textContent.toLowerCase().includes(query);
+        shown += row.hidden ? 0 : 1;
+    }
+    document.getElementById("shown").textContent = shown;
+});
+
+headings.forEach((heading, column) => {
+    heading.addEventListener("click", () => {
+        const numeric = heading.classList.contains("num");
+        const ascending = heading.dataset.dir ? heading.dataset.dir === "desc" : !numeric;
+        headings.forEach((h) => delete h.dataset.dir);
+        heading.dataset.dir = ascending ? "asc" : "desc";
+
+        const key = (row) => {
+            const text = row.cells[column].textContent;
+            return numeric ? (text === "" ? -Infinity : parseFloat(text)) : text.toLowerCase();
+        };
+        rows.sort((a, b) => {
+            const [x, y] = [key(a), key(b)];
+            const order = x < y ? -1 : x > y ? 1 : 0;
+            return ascending ? order : -order;
+        });
+        body.append(...rows);
+    });
+});
+"#;
+
+pub fn write_html(file: &mut impl Write, results: Results, run_info: &RunInfo) -> Result<()> {
+    writeln!(file, "<!DOCTYPE html>")?;
+    writeln!(file, "<html>\n<head>")?;
+    writeln!(file, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        file,
+        "<title>wikidigest-link-count: {}</title>",
+        escape(run_info.mode.name())
+    )?;
+    writeln!(file, "<style>{}</style>", STYLE)?;
+    writeln!(file, "</head>\n<body>")?;
+
+    // Summary of run parameters
+    writeln!(
+        file,
+        "<h1>wikidigest-link-count: {}</h1>",
+        escape(run_info.mode.name())
+    )?;
+    writeln!(file, "<dl>")?;
+    for (table, path) in &run_info.inputs {
+        writeln!(
+            file,
+            "<dt>{} file</dt><dd>{}</dd>",
+            escape(table),
+            escape(&path.display().to_string())
+        )?;
+    }
+    writeln!(
+        file,
+        "<dt>Namespaces from</dt><dd>{}</dd>",
+        namespace_list(&run_info.namespaces_from)
+    )?;
+    writeln!(
+        file,
+        "<dt>Namespaces to</dt><dd>{}</dd>",
+        namespace_list(&run_info.namespaces_to)
+    )?;
+    writeln!(file, "<dt>Cutoff</dt><dd>{}</dd>", run_info.cutoff)?;
+    writeln!(
+        file,
+        "<dt>Rows</dt><dd><span id=\"shown\">{0}</span> of {0}</dd>",
+        results.entries.len()
+    )?;
+    writeln!(file, "</dl>")?;
+    writeln!(
+        file,
+        "<input id=\"filter\" type=\"search\" placeholder=\"Filter rows\">"
+    )?;
+
+    // Additional columns are sorted as numbers if their first value is a number
+    let numeric: Vec<bool> = (0..results.columns.len())
+        .map(|i| {
+            results
+                .entries
+                .iter()
+                .map(|e| &e.extra[i])
+                .find(|value| !matches!(value, Value::Empty))
+                .is_some_and(|value| matches!(value, Value::Int(_) | Value::Float(_)))
+        })
+        .collect();
+
+    writeln!(file, "<table id=\"results\">\n<thead><tr>")?;
+    match results.name_heading {
+        Some(heading) => writeln!(file, "<th>{}</th>", escape(heading))?,
+        None => writeln!(
+            file,
+            "<th>Page</th><th>Namespace</th><th class=\"num\">Links total</th>\
+            <th class=\"num\">Direct</th><th class=\"num\">via redirect</th>"
+        )?,
+    }
+    for (column, &is_numeric) in results.columns.iter().zip(&numeric) {
+        let class = if is_numeric { " class=\"num\"" } else { "" };
+        writeln!(file, "<th{}>{}</th>", class, escape(column))?;
+    }
+    writeln!(file, "</tr></thead>\n<tbody>")?;
+
+    for e in results.entries {
+        write!(file, "<tr>")?;
+        match e.key {
+            Key::Page((ns, title)) => {
+                let title = util::sql_unescape(&title.0);
+                let display = escape(&util::underscores_to_spaces(title.clone()));
+                match namespace::name(ns) {
+                    Some(prefix) => {
+                        let full_title = if prefix.is_empty() {
+                            title
+                        } else {
+                            format!("{}:{}", prefix.replace(' ', "_"), title)
+                        };
+                        write!(
+                            file,
+                            "<td><a href=\"{}{}\">{}</a></td>",
+                            escape(&run_info.wiki_url),
+                            url_encode(&full_title),
+                            display
+                        )?
+                    }
+                    // Pages in unknown namespaces can't be linked to by title
+                    None => write!(file, "<td>{}</td>", display)?,
+                }
+                write!(
+                    file,
+                    "<td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                    <td class=\"num\">{}</td>",
+                    escape(&namespace_name(ns)),
+                    e.links.total(),
+                    e.links.direct,
+                    e.links.indirect
+                )?;
+            }
+            Key::Name(name) => write!(file, "<td>{}</td>", escape(&util::sql_unescape(&name)))?,
+        }
+        for (value, &is_numeric) in e.extra.iter().zip(&numeric) {
+            let class = if is_numeric { " class=\"num\"" } else { "" };
+            write!(file, "<td{}>{}</td>", class, escape(&value.to_string()))?;
+        }
+        writeln!(file, "</tr>")?;
+    }
+
+    writeln!(file, "</tbody>\n</table>")?;
+    writeln!(file, "<script>{}</script>", SCRIPT)?;
+    writeln!(file, "</body>\n</html>")?;
+    Ok(())
+}
+
+// Canonical namespace name, e.g. ‘Talk’, or the number if unknown
+fn namespace_name(ns: PageNs) -> String {
+    match namespace::name(ns) {
+        Some("") => "(Main)".to_string(),
+        Some(name) => name.to_string(),
+        None => ns.to_string(),
+    }
+}
+
+fn namespace_list(namespaces: &[PageNs]) -> String {
+    if namespaces.is_empty() {
+        return "all".to_string();
+    }
+    let names: Vec<String> = namespaces
+        .iter()
+        .map(|&ns| escape(&namespace_name(ns)))
+        .collect();
+    names.join(", ")
+}
+
+fn escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+// Percent-encode a title for use in a URL path, leaving the characters MediaWiki leaves as is
+fn url_encode(s: &str) -> String {
+    use std::fmt::Write;
+
+    let mut output = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => output.push(b as char),
+            b'-' | b'_' | b'.' | b'~' | b':' | b'/' | b'(' | b')' | b'!' | b',' | b'*' | b';'
+            | b'@' | b'$' => output.push(b as char),
+            _ => write!(&mut output, "%{:02X}", b).unwrap(),
+        }
+    }
+    output
+}
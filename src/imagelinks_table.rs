@@ -0,0 +1,133 @@
+/*
+Process SQL dumps for the MediaWiki “imagelinks” table, i.e. count the pages using each file. Both
+the legacy schema (with the file title per row) and the newer one (referring to the “linktarget”
+table by ID) are supported.
+*/
+use crate::{
+    linktarget_table::LinkTargets,
+    pagelinks_table::{LinkCounter, LinkRow, PageLinks},
+    redirect_table::RedirectMap,
+    sql_dump::{self, RowParser},
+    util::{self, PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashSet;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+const FILE_NS: PageNs = PageNs(6);
+
+// If ‘linktargets’ is given, rows are expected to be in the newer, linktarget-based schema
+pub struct ImagelinksParser {
+    regex: Regex,
+    linktargets: Option<LinkTargets>,
+}
+
+impl ImagelinksParser {
+    pub fn new(namespaces_from: &[PageNs], linktargets: Option<LinkTargets>) -> Result<Self> {
+        let regex = match &linktargets {
+            Some(_) => build_imagelinks_regex(namespaces_from)?,
+            None => build_legacy_imagelinks_regex(namespaces_from)?,
+        };
+        Ok(Self { regex, linktargets })
+    }
+}
+
+impl RowParser for ImagelinksParser {
+    type Row<'a> = LinkRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<LinkRow<'a>> {
+        let from = PageId(cap[1].parse::<u32>().unwrap());
+        match &self.linktargets {
+            Some(linktargets) => {
+                // Link targets are only mapped within the file namespace
+                let (_, title) = linktargets.get(&cap[3].parse::<u64>().unwrap())?;
+                Some(LinkRow {
+                    from,
+                    from_ns: PageNs(cap[2].parse::<u32>().unwrap()),
+                    ns: FILE_NS,
+                    title: title.0.as_str(),
+                })
+            }
+            None => Some(LinkRow {
+                from,
+                from_ns: PageNs(cap[3].parse::<u32>().unwrap()),
+                ns: FILE_NS,
+                title: cap.get(2).unwrap().as_str(),
+            }),
+        }
+    }
+}
+
+// Count pages using each file, with uses of file redirects added to their target (as indirect)
+pub fn count_file_usage<T>(
+    source: T,
+    redirects: RedirectMap,
+    linktargets: Option<LinkTargets>,
+    namespaces_from: &[PageNs],
+    targets: Option<&AHashSet<(PageNs, PageTitle)>>,
+    buffer_size: usize,
+) -> Result<PageLinks>
+where
+    T: Read + Send,
+{
+    let parser = ImagelinksParser::new(namespaces_from, linktargets)?;
+    let counter = LinkCounter {
+        redirects: &redirects,
+        targets,
+        exclusions: None,
+        namespaces: None,
+        per_redirect: false,
+    };
+
+    let counts = sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘imagelinks’ table data and counting file usage",
+        buffer_size,
+    )?;
+
+    Ok(counts.links)
+}
+
+/*
+Regex Pattern: \((\d+),({}),(\d+)\)
+
+(\d+) : match and capture the ‘il_from’ field.
+
+,({}), : match and capture the ‘il_from_namespace’ on any of the given numbers (e.g. 0|5|7) passed
+via the function parameter.
+
+(\d+) : match and capture the ‘il_target_id’ field, referring to ‘lt_id’ of the linktarget table.
+*/
+fn build_imagelinks_regex(namespaces_from: &[PageNs]) -> Result<Regex> {
+    let ns_from_str = util::namespaces_to_string(namespaces_from);
+
+    let pattern = format!(r"\((\d+),({}),(\d+)\)", ns_from_str);
+    Regex::new(&pattern).context("Building imagelinks regex")
+}
+
+/*
+Regex Pattern: \((\d+),'((?:[^']|\\'){1,255}?)',({})\)
+
+(\d+) : match and capture the ‘il_from’ field.
+
+'((?:[^']|\\'){1,255}?)' : match and capture ‘il_to’, the title of the file (without namespace
+prefix).
+
+,({}) : match and capture the ‘il_from_namespace’ on any of the given numbers (e.g. 0|5|7) passed
+via the function parameter.
+*/
+fn build_legacy_imagelinks_regex(namespaces_from: &[PageNs]) -> Result<Regex> {
+    let ns_from_str = util::namespaces_to_string(namespaces_from);
+
+    let pattern = format!(r"\((\d+),'((?:[^']|\\'){{1,255}}?)',({})\)", ns_from_str);
+    Regex::new(&pattern).context("Building imagelinks regex")
+}
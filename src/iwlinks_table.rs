@@ -0,0 +1,130 @@
+/*
+Process SQL dumps for the MediaWiki “iwlinks” table, i.e. count links per interwiki prefix and
+title (e.g. ‘wikt:example’ or ‘commons:Category:Maps’).
+*/
+use crate::{
+    link_count::LinkCount,
+    page_table::PageIndex,
+    results::Value,
+    sql_dump::{self, Aggregator, RowParser},
+    util::{self, PageId, PageNs},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+pub type InterwikiLinks = AHashMap<(String, String), LinkCount>;
+
+pub struct InterwikiRow<'a> {
+    pub from: PageId,
+    pub prefix: &'a str,
+    pub title: &'a str,
+}
+
+pub struct IwlinksParser {
+    regex: Regex,
+}
+
+impl RowParser for IwlinksParser {
+    type Row<'a> = InterwikiRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<InterwikiRow<'a>> {
+        Some(InterwikiRow {
+            from: PageId(cap[1].parse::<u32>().unwrap()),
+            prefix: cap.get(2).unwrap().as_str(),
+            title: cap.get(3).unwrap().as_str(),
+        })
+    }
+}
+
+// Count links from pages within the given namespaces, optionally only for the given prefixes
+struct InterwikiCounter<'a> {
+    index: &'a PageIndex,
+    namespaces: &'a [PageNs],
+    prefixes: &'a [String],
+}
+
+impl<'r> Aggregator<InterwikiRow<'r>> for InterwikiCounter<'_> {
+    type Output = InterwikiLinks;
+
+    fn add(&self, output: &mut InterwikiLinks, row: InterwikiRow<'r>) {
+        // The index may also hold pages outside the source namespaces
+        match self.index.get(&row.from) {
+            Some(page) if self.namespaces.contains(&page.ns) => (),
+            _ => return,
+        }
+        if !self.prefixes.is_empty() && !self.prefixes.iter().any(|p| p == row.prefix) {
+            return;
+        }
+
+        let key = (row.prefix.to_string(), row.title.to_string());
+        output.entry(key).or_default().direct += 1;
+    }
+
+    fn merge(&self, output: &mut InterwikiLinks, partial: InterwikiLinks) {
+        for (link, new_counter) in partial {
+            *output.entry(link).or_default() += new_counter;
+        }
+    }
+}
+
+pub fn count_interwiki<T>(
+    source: T,
+    index: &PageIndex,
+    namespaces: &[PageNs],
+    prefixes: &[String],
+    buffer_size: usize,
+) -> Result<InterwikiLinks>
+where
+    T: Read + Send,
+{
+    let parser = IwlinksParser {
+        regex: build_iwlinks_regex()?,
+    };
+    let counter = InterwikiCounter {
+        index,
+        namespaces,
+        prefixes,
+    };
+
+    sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘iwlinks’ table data and counting links",
+        buffer_size,
+    )
+}
+
+// Output rows as ‘prefix:Title’, with their link count
+pub fn to_rows<I>(iwlinks: I) -> impl Iterator<Item = (String, Vec<Value>)>
+where
+    I: IntoIterator<Item = ((String, String), LinkCount)>,
+{
+    iwlinks.into_iter().map(|((prefix, title), links)| {
+        let name = format!("{}:{}", prefix, util::underscores_to_spaces(title));
+        (name, vec![Value::Int(u64::from(links.total()))])
+    })
+}
+
+/*
+Regex Pattern: \((\d+),'((?:[^'\\]|\\.){1,32}?)','((?:[^'\\]|\\.)*)'\)
+
+(\d+) : match and capture the ‘iwl_from’ field.
+
+'((?:[^'\\]|\\.){1,32}?)' : match and capture the ‘iwl_prefix’ field, e.g. ‘wikt’.
+
+'((?:[^'\\]|\\.)*)' : match and capture the ‘iwl_title’ field, which may be empty and contain any
+escaped character.
+*/
+fn build_iwlinks_regex() -> Result<Regex> {
+    let pattern = r"\((\d+),'((?:[^'\\]|\\.){1,32}?)','((?:[^'\\]|\\.)*)'\)";
+    Regex::new(pattern).context("Building iwlinks regex")
+}
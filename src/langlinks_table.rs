@@ -0,0 +1,107 @@
+/*
+Process SQL dumps for the MediaWiki “langlinks” table, i.e. find the number of language editions
+each page is linked to, and whether it is linked to a particular one.
+*/
+use crate::{
+    page_table::{self, PageIndex, PageRecord, PageRef},
+    sql_dump::RowParser,
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+#[derive(Clone, Copy, Default)]
+pub struct Languages {
+    // Number of language editions linked to
+    pub editions: u32,
+    // Whether one of them is the requested language
+    pub has_language: bool,
+}
+
+pub type PageLanguages = AHashMap<(PageNs, PageTitle), Languages>;
+
+pub struct LanguageRow {
+    pub from: PageId,
+    // Link is to the requested language edition
+    pub is_language: bool,
+}
+
+impl PageRef for LanguageRow {
+    fn page(&self) -> PageId {
+        self.from
+    }
+}
+
+pub struct LanglinksParser {
+    regex: Regex,
+    language: Option<String>,
+}
+
+impl RowParser for LanglinksParser {
+    type Row<'a> = LanguageRow;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<LanguageRow> {
+        Some(LanguageRow {
+            from: PageId(cap[1].parse::<u32>().unwrap()),
+            is_language: self.language.as_deref() == Some(&cap[2]),
+        })
+    }
+}
+
+impl PageRecord<LanguageRow> for Languages {
+    fn add(&mut self, row: LanguageRow) {
+        self.editions += 1;
+        self.has_language |= row.is_language;
+    }
+
+    fn merge(&mut self, other: Languages) {
+        self.editions += other.editions;
+        self.has_language |= other.has_language;
+    }
+}
+
+// Collect interlanguage links of pages within the index, noting those to ‘language’, if given
+pub fn collect_languages<T>(
+    source: T,
+    index: &PageIndex,
+    language: Option<&str>,
+    buffer_size: usize,
+) -> Result<PageLanguages>
+where
+    T: Read + Send,
+{
+    let parser = LanglinksParser {
+        regex: build_langlinks_regex()?,
+        language: language.map(String::from),
+    };
+
+    page_table::collect_records(
+        source,
+        &parser,
+        index,
+        "Extracting ‘langlinks’ table data",
+        buffer_size,
+    )
+}
+
+/*
+Regex Pattern: \((\d+),'([a-z0-9-]+)','(?:[^'\\]|\\.)*'\)
+
+(\d+) : match and capture the ‘ll_from’ field.
+
+'([a-z0-9-]+)' : match and capture the ‘ll_lang’ field, a language code such as ‘de’ or ‘zh-yue’.
+
+'(?:[^'\\]|\\.)*' : match the ‘ll_title’ field, which may contain any escaped character.
+*/
+fn build_langlinks_regex() -> Result<Regex> {
+    let pattern = r"\((\d+),'([a-z0-9-]+)','(?:[^'\\]|\\.)*'\)";
+    Regex::new(pattern).context("Building langlinks regex")
+}
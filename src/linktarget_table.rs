@@ -0,0 +1,89 @@
+/*
+Process SQL dumps for the MediaWiki “linktarget” table, which newer link tables (e.g. templatelinks)
+refer to by ID instead of storing namespace and title themselves.
+*/
+use crate::{
+    sql_dump::{self, Aggregator, RowParser},
+    util::{self, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+pub type LinkTargets = AHashMap<u64, (PageNs, PageTitle)>;
+
+pub struct LinktargetParser {
+    regex: Regex,
+}
+
+impl RowParser for LinktargetParser {
+    type Row<'a> = (u64, PageNs, &'a str);
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<Self::Row<'a>> {
+        Some((
+            cap[1].parse::<u64>().unwrap(),
+            PageNs(cap[2].parse::<u32>().unwrap()),
+            cap.get(3).unwrap().as_str(),
+        ))
+    }
+}
+
+struct LinktargetMapper;
+
+impl<'r> Aggregator<(u64, PageNs, &'r str)> for LinktargetMapper {
+    type Output = LinkTargets;
+
+    fn add(&self, output: &mut LinkTargets, (id, ns, title): (u64, PageNs, &'r str)) {
+        output.insert(id, (ns, PageTitle(title.to_string())));
+    }
+
+    fn merge(&self, output: &mut LinkTargets, partial: LinkTargets) {
+        output.extend(partial);
+    }
+}
+
+pub fn map_linktargets<T>(
+    source: T,
+    namespaces: &[PageNs],
+    buffer_size: usize,
+) -> Result<LinkTargets>
+where
+    T: Read + Send,
+{
+    let parser = LinktargetParser {
+        regex: build_linktarget_regex(namespaces)?,
+    };
+
+    sql_dump::process(
+        source,
+        &parser,
+        &LinktargetMapper,
+        "Extracting ‘linktarget’ table data",
+        buffer_size,
+    )
+}
+
+/*
+Regex Pattern: \((\d+),({}),'((?:[^']|\\'){1,255}?)'\)
+
+(\d+) : match and capture the ‘lt_id’ field.
+
+,({}), : match and capture ‘lt_namespace’ on any of the given numbers (e.g. 0|5|7) passed via the
+function parameter.
+
+'((?:[^']|\\'){1,255}?)' : match and capture as ‘lt_title’, any UTF-8 sequence of up to 255 bytes
+that does not contain ' except if escaped as \'.
+*/
+fn build_linktarget_regex(namespaces: &[PageNs]) -> Result<Regex> {
+    let ns_str = util::namespaces_to_string(namespaces);
+
+    let pattern = format!(r"\((\d+),({}),'((?:[^']|\\'){{1,255}}?)'\)", ns_str);
+    Regex::new(&pattern).context("Building linktarget regex")
+}
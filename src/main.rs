@@ -1,15 +1,40 @@
 mod buffer_queue;
+mod category_tree;
+mod categorylinks_table;
 mod chunked_reader;
 mod cli;
+mod externallinks_table;
+mod html_report;
+mod imagelinks_table;
+mod iwlinks_table;
+mod langlinks_table;
 mod link_count;
+mod linktarget_table;
+mod maintenance;
+mod namespace;
+mod page_props_table;
+mod page_restrictions_table;
 mod page_table;
 mod pagelinks_table;
 mod progress_display;
+mod redirect_categories;
 mod redirect_table;
+mod results;
+mod sql_dump;
+mod stats;
+mod targets;
+mod templatelinks_table;
 mod util;
 
+use page_table::{IndexScope, Pages};
+use pagelinks_table::{CountOptions, Exclusions, LinkCounts};
+use results::{Columns, Results, Rows, UnsortedResults, Value};
+use stats::LinkStats;
+use targets::Targets;
+use util::{Mode, PageNs};
+
+use ahash::{AHashMap, AHashSet};
 use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
 
 use std::fs::File;
 
@@ -27,65 +52,633 @@ fn main() -> Result<()> {
         (file, path)
     };
 
+    // Read list of target pages, if counting is to be restricted to these
+    let mut targets = if cli.targets_file.is_some() || !cli.target_titles.is_empty() {
+        let mut targets = Targets::default();
+        if let Some(path) = &cli.targets_file {
+            targets.read_file(path)?;
+        }
+        for title in &cli.target_titles {
+            targets.add(title)?;
+        }
+        Some(targets)
+    } else {
+        None
+    };
+    let lookup_ids = match &targets {
+        Some(targets) => targets.ids.clone(),
+        None => AHashSet::new(),
+    };
+
+    // Checking for existence of pages, or resolving source page IDs, requires a complete index
+    let mut index_namespaces = Vec::new();
+    if matches!(
+        cli.mode,
+        Mode::Wanted | Mode::Orphans | Mode::CategoryRollup | Mode::Stubs
+    ) || cli.mark_missing
+        || cli.langlinks_file.is_some()
+        || cli.page_props_file.is_some()
+        || cli.page_restrictions_file.is_some()
+        || !cli.target_categories.is_empty()
+        || cli.by_redirect_category
+        || cli.mode == Mode::RedirectCategories
+    {
+        index_namespaces.extend_from_slice(&cli.namespaces_to);
+    }
+    // Subcategories are found by their page ID
+    // In ‘category-rollup’ mode, target categories are the roots to roll up instead
+    let use_category_trees = (!cli.target_categories.is_empty()
+        && cli.mode != Mode::CategoryRollup)
+        || !cli.source_categories.is_empty();
+    if use_category_trees || cli.mode == Mode::CategoryRollup {
+        index_namespaces.push(PageNs(14));
+    }
+    if matches!(
+        cli.mode,
+        Mode::Outgoing
+            | Mode::DeadEnds
+            | Mode::WhatLinksHere
+            | Mode::Categories
+            | Mode::Domains
+            | Mode::Interwiki
+    ) || cli.exclude_self_links
+        || cli.exclude_redirect_sources
+    {
+        index_namespaces.extend_from_slice(&cli.namespaces_from);
+    }
+    let scope = if cli.mode == Mode::Categories && cli.namespaces_from.is_empty() {
+        IndexScope::All
+    } else if index_namespaces.is_empty() {
+        IndexScope::Ids(&lookup_ids)
+    } else {
+        IndexScope::Namespaces(index_namespaces)
+    };
+
     // Process page-table data
-    let pages = {
-        let f = File::open(&cli.page_file)
-            .with_context(|| format!("Failed to open page file ‘{}’", &cli.page_file.display()))?;
-        if util::is_probably_gzip(&cli.page_file) {
-            page_table::collect_pages(GzDecoder::new(f), &cli.namespaces_to, buf_size)
-        } else {
-            page_table::collect_pages(f, &cli.namespaces_to, buf_size)
+    let Pages {
+        redirects: redirect_pages,
+        index,
+    } = page_table::collect_pages(
+        util::open_dump(&cli.page_file, "page")?,
+        &cli.namespaces_to,
+        scope,
+        buf_size,
+    )?;
+
+    if let Some(targets) = &mut targets {
+        targets.resolve_ids(&index);
+        for (ns, title) in &targets.pages {
+            if !cli.namespaces_to.contains(ns) {
+                eprintln!(
+                    "Warning: target ‘{}’ [{}] is outside of the ‘to’ namespaces",
+                    title, ns
+                );
+            }
         }
-    }?;
+    }
+
+    let existing_pages: Option<AHashSet<_>> = if cli.mode == Mode::Wanted
+        || cli.mark_missing
+        || cli.missing_language.is_some()
+        || cli.missing_wikidata_item
+        || cli.unprotected
+    {
+        Some(
+            index
+                .values()
+                .map(|page| (page.ns, page.title.clone()))
+                .collect(),
+        )
+    } else {
+        None
+    };
 
     // Process redirect-table data
-    let redirects = {
-        let f = File::open(&cli.redirect_file).with_context(|| {
-            format!(
-                "Failed to open redirect file ‘{}’",
-                &cli.redirect_file.display()
-            )
-        })?;
-        if util::is_probably_gzip(&cli.redirect_file) {
-            redirect_table::map_redirects(GzDecoder::new(f), pages, &cli.namespaces_to, buf_size)
-        } else {
-            redirect_table::map_redirects(f, pages, &cli.namespaces_to, buf_size)
+    let target_pages = targets.as_ref().map(|t| &t.pages);
+    let mut redirects = redirect_table::map_redirects(
+        util::open_dump(&cli.redirect_file, "redirect")?,
+        redirect_pages,
+        &cli.namespaces_to,
+        target_pages,
+        buf_size,
+    )?;
+
+    if let Some(targets) = &mut targets {
+        targets.follow_redirects(&mut redirects);
+    }
+    let target_pages = targets.as_ref().map(|t| &t.pages);
+
+    // Process langlinks-table data
+    let languages = match &cli.langlinks_file {
+        Some(path) => Some(langlinks_table::collect_languages(
+            util::open_dump(path, "langlinks")?,
+            &index,
+            cli.missing_language.as_deref(),
+            buf_size,
+        )?),
+        None => None,
+    };
+
+    // Process page_props-table data
+    let page_props = match &cli.page_props_file {
+        Some(path) => Some(page_props_table::collect_page_props(
+            util::open_dump(path, "page_props")?,
+            &index,
+            buf_size,
+        )?),
+        None => None,
+    };
+
+    // Process page_restrictions-table data
+    let protection = match &cli.page_restrictions_file {
+        Some(path) => Some(page_restrictions_table::collect_protection(
+            util::open_dump(path, "page_restrictions")?,
+            &index,
+            buf_size,
+        )?),
+        None => None,
+    };
+
+    // Find pages within the requested category trees
+    let mut target_members = None;
+    let mut source_members = None;
+    if use_category_trees {
+        let path = cli.categorylinks_file.as_ref().unwrap();
+        let subcategories = category_tree::collect_subcategories(
+            util::open_dump(path, "categorylinks")?,
+            buf_size,
+        )?;
+        let trees = [
+            subcategories.tree(&cli.target_categories, cli.category_depth, &index),
+            subcategories.tree(&cli.source_categories, cli.category_depth, &index),
+        ];
+        let mut members = category_tree::collect_members(
+            util::open_dump(path, "categorylinks")?,
+            &trees,
+            buf_size,
+        )?
+        .into_iter();
+        let (targets, sources) = (members.next().unwrap(), members.next().unwrap());
+
+        if !cli.target_categories.is_empty() {
+            eprintln!(
+                "Target category tree: {} categories, {} members",
+                trees[0].len(),
+                targets.len()
+            );
+            let pages: AHashSet<_> = targets
+                .iter()
+                .filter_map(|id| index.get(id))
+                .filter(|page| cli.namespaces_to.contains(&page.ns))
+                .map(|page| (page.ns, page.title.clone()))
+                .collect();
+            target_members = Some(pages);
         }
-    }?;
-
-    // Process pagelinks-table data
-    let pagelinks = {
-        let f = File::open(&cli.pagelinks_file).with_context(|| {
-            format!(
-                "Failed to open pagelinks file ‘{}’",
-                &cli.pagelinks_file.display()
-            )
-        })?;
-        if util::is_probably_gzip(&cli.pagelinks_file) {
-            pagelinks_table::count_links(
-                GzDecoder::new(f),
+        if !cli.source_categories.is_empty() {
+            eprintln!(
+                "Source category tree: {} categories, {} members",
+                trees[1].len(),
+                sources.len()
+            );
+            source_members = Some(sources);
+        }
+    }
+
+    // Process links-table data
+    let mut category_sizes = None;
+    let mut category_rollups = None;
+    let mut redirect_categories = None;
+    let page_sizes: AHashMap<_, _> = if cli.mode == Mode::Stubs {
+        index
+            .values()
+            .map(|page| ((page.ns, page.title.clone()), page.len))
+            .collect()
+    } else {
+        AHashMap::new()
+    };
+    // Additional columns, added to the rows once these are final
+    let mut columns = Columns::default();
+    let open_pagelinks = || util::open_dump(cli.pagelinks_file.as_ref().unwrap(), "pagelinks");
+    let rows = if cli.mode == Mode::WhatLinksHere {
+        let backlinks = pagelinks_table::find_backlinks(
+            open_pagelinks()?,
+            redirects,
+            (&cli.namespaces_from, &cli.namespaces_to),
+            target_pages.unwrap(),
+            buf_size,
+        )?;
+
+        eprint!("4/5 Sorting links (...)");
+        let results = Results::from_backlinks(backlinks, &index);
+        eprintln!(" Done.");
+        Rows::Sorted(results)
+    } else if cli.mode == Mode::Domains {
+        let domains = externallinks_table::count_domains(
+            util::open_dump(cli.externallinks_file.as_ref().unwrap(), "externallinks")?,
+            &index,
+            &cli.namespaces_from,
+            cli.url_prefix_depth,
+            buf_size,
+        )?;
+
+        eprint!("4/5 Sorting domains (...)");
+        let heading = match cli.url_prefix_depth {
+            Some(_) => "URL prefix",
+            None => "Domain",
+        };
+        let domains = externallinks_table::sort_domains(domains, cli.cutoff_threshold);
+        let results = Results::from_names(heading, vec!["Pages", "Links"], domains);
+        eprintln!(" Done.");
+        Rows::Sorted(results)
+    } else if cli.mode == Mode::Interwiki {
+        let iwlinks = iwlinks_table::count_interwiki(
+            util::open_dump(cli.iwlinks_file.as_ref().unwrap(), "iwlinks")?,
+            &index,
+            &cli.namespaces_from,
+            &cli.interwiki_prefixes,
+            buf_size,
+        )?;
+
+        eprint!("4/5 Sorting links (...)");
+        let rows = if cli.unsorted {
+            // Rows are only produced as they are written out
+            let iwlinks = util::filter_pagelinks(iwlinks, cli.cutoff_threshold);
+            Rows::Unsorted(UnsortedResults::from_names(
+                vec!["Links"],
+                iwlinks_table::to_rows(iwlinks),
+            ))
+        } else {
+            let iwlinks = util::sort_pagelinks(iwlinks, cli.cutoff_threshold);
+            Rows::Sorted(Results::from_names(
+                "Interwiki link",
+                vec!["Links"],
+                iwlinks_table::to_rows(iwlinks).collect(),
+            ))
+        };
+        eprintln!(" Done.");
+        rows
+    } else {
+        let (mut pagelinks, outlinks) = if matches!(cli.mode, Mode::Outgoing | Mode::DeadEnds) {
+            // A link into any namespace (e.g. to a category) keeps a page from being a dead end
+            let namespaces_to: &[PageNs] = if cli.mode == Mode::DeadEnds {
+                &[]
+            } else {
+                &cli.namespaces_to
+            };
+            let outlinks = pagelinks_table::count_outgoing(
+                open_pagelinks()?,
                 redirects,
-                (&cli.namespaces_from, &cli.namespaces_to),
+                (&cli.namespaces_from, namespaces_to),
+                target_pages,
+                buf_size,
+            )?;
+            (AHashMap::new(), outlinks)
+        } else if matches!(cli.mode, Mode::Templates | Mode::Files) {
+            // Newer dumps refer to templates and files via the linktarget table
+            let linktargets = match &cli.linktarget_file {
+                Some(path) => Some(linktarget_table::map_linktargets(
+                    util::open_dump(path, "linktarget")?,
+                    &cli.namespaces_to,
+                    buf_size,
+                )?),
+                None => None,
+            };
+
+            let usage = if cli.mode == Mode::Templates {
+                templatelinks_table::count_transclusions(
+                    util::open_dump(cli.templatelinks_file.as_ref().unwrap(), "templatelinks")?,
+                    redirects,
+                    linktargets,
+                    (&cli.namespaces_from, &cli.namespaces_to),
+                    target_pages,
+                    buf_size,
+                )?
+            } else {
+                imagelinks_table::count_file_usage(
+                    util::open_dump(cli.imagelinks_file.as_ref().unwrap(), "imagelinks")?,
+                    redirects,
+                    linktargets,
+                    &cli.namespaces_from,
+                    target_pages,
+                    buf_size,
+                )?
+            };
+            (usage, AHashMap::new())
+        } else if cli.mode == Mode::Categories {
+            let categories = categorylinks_table::count_members(
+                util::open_dump(cli.categorylinks_file.as_ref().unwrap(), "categorylinks")?,
+                redirects,
+                &index,
+                &cli.namespaces_from,
+                target_pages,
                 buf_size,
-            )
+            )?;
+            let members = categories
+                .iter()
+                .map(|(category, size)| (category.clone(), size.members))
+                .collect();
+            category_sizes = Some(categories);
+            (members, AHashMap::new())
         } else {
-            pagelinks_table::count_links(
-                f,
+            let exclusions = if cli.exclude_self_links
+                || cli.exclude_redirect_sources
+                || source_members.is_some()
+            {
+                Some(Exclusions {
+                    index: &index,
+                    self_links: cli.exclude_self_links,
+                    redirect_sources: cli.exclude_redirect_sources,
+                    sources: source_members.as_ref(),
+                })
+            } else {
+                None
+            };
+
+            let redirect_count = redirects.len();
+            let per_redirect = cli.by_redirect_category || cli.mode == Mode::RedirectCategories;
+            let LinkCounts {
+                links: mut pagelinks,
+                rows: row_counts,
+                redirect_links,
+            } = pagelinks_table::count_links(
+                open_pagelinks()?,
                 redirects,
                 (&cli.namespaces_from, &cli.namespaces_to),
+                &CountOptions {
+                    targets: target_pages,
+                    exclusions: exclusions.as_ref(),
+                    scan_all_rows: cli.stats_file.is_some(),
+                    per_redirect,
+                },
                 buf_size,
-            )
+            )?;
+
+            if cli.exclude_self_links || cli.exclude_redirect_sources {
+                eprintln!(
+                    "Excluded {} self-links and {} links from redirect pages",
+                    row_counts.self_links, row_counts.redirect_sources
+                );
+            }
+            if source_members.is_some() {
+                eprintln!(
+                    "Excluded {} links from pages outside the source categories",
+                    row_counts.other_sources
+                );
+            }
+
+            if let Some(path) = &cli.stats_file {
+                eprint!("Writing statistics to {} (...)", path.display());
+                LinkStats::new(&pagelinks, row_counts, redirect_count).write_to_file(path)?;
+                eprintln!(" Done.");
+            }
+
+            if per_redirect {
+                redirect_categories = Some(redirect_categories::collect(
+                    util::open_dump(cli.categorylinks_file.as_ref().unwrap(), "categorylinks")?,
+                    &redirect_links,
+                    &index,
+                    buf_size,
+                )?);
+            }
+
+            // Replace page link counts with the totals of the categories they are in
+            if cli.mode == Mode::CategoryRollup {
+                let path = cli.categorylinks_file.as_ref().unwrap();
+                let rollups = if cli.target_categories.is_empty() {
+                    category_tree::roll_up(
+                        util::open_dump(path, "categorylinks")?,
+                        &pagelinks,
+                        &index,
+                        &cli.namespaces_to,
+                        buf_size,
+                    )?
+                } else {
+                    // Only the members of each root's tree are kept
+                    let mut roots = cli.target_categories.clone();
+                    roots.sort_unstable();
+                    roots.dedup();
+                    let subcategories = category_tree::collect_subcategories(
+                        util::open_dump(path, "categorylinks")?,
+                        buf_size,
+                    )?;
+                    let trees: Vec<_> = roots
+                        .iter()
+                        .map(|root| {
+                            let root = std::slice::from_ref(root);
+                            subcategories.tree(root, cli.category_depth, &index)
+                        })
+                        .collect();
+                    let members = category_tree::collect_members(
+                        util::open_dump(path, "categorylinks")?,
+                        &trees,
+                        buf_size,
+                    )?;
+                    category_tree::roll_up_trees(
+                        &roots,
+                        &members,
+                        &pagelinks,
+                        &index,
+                        &cli.namespaces_to,
+                    )
+                };
+                pagelinks = rollups
+                    .iter()
+                    .map(|(category, rollup)| (category.clone(), rollup.links))
+                    .collect();
+                category_rollups = Some(rollups);
+            }
+            (pagelinks, AHashMap::new())
+        };
+
+        eprint!("4/5 Sorting pages (...)");
+        let rows = match cli.mode {
+            // Maintenance reports list all matching pages, in order of title (stubs by density)
+            Mode::Orphans => Rows::Sorted(Results::new(maintenance::find_orphans(
+                &index,
+                &pagelinks,
+                &cli.namespaces_to,
+                cli.orphans_via_redirects,
+            ))),
+            Mode::DeadEnds => Rows::Sorted(Results::new(maintenance::find_dead_ends(
+                &index,
+                &outlinks,
+                &cli.namespaces_from,
+            ))),
+            // Only the totals per redirect category are output
+            Mode::RedirectCategories => {
+                let totals = redirect_categories.take().unwrap().totals;
+                Rows::Sorted(Results::from_names(
+                    "Redirect category",
+                    vec!["Links", "Redirects"],
+                    redirect_categories::sort_totals(totals, cli.cutoff_threshold),
+                ))
+            }
+            Mode::Stubs => Rows::Sorted(Results::new(maintenance::find_stubs(
+                &index,
+                &pagelinks,
+                &cli.namespaces_to,
+                cli.max_bytes,
+                cli.min_links,
+            ))),
+            _ => {
+                if cli.mode == Mode::Outgoing {
+                    // Resolve source page IDs to titles
+                    pagelinks = outlinks
+                        .into_iter()
+                        .filter_map(|(id, link_count)| {
+                            let page = index.get(&id)?;
+                            Some(((page.ns, page.title.clone()), link_count))
+                        })
+                        .collect();
+                }
+
+                // Explicitly listed targets are kept regardless of their link count, but are
+                // subject to the filters below like any other page
+                let cutoff = match &targets {
+                    Some(targets) if cli.mode != Mode::Outgoing => {
+                        targets.fill_missing(&mut pagelinks);
+                        0
+                    }
+                    _ => cli.cutoff_threshold,
+                };
+
+                // Only keep links to non-existent pages
+                if cli.mode == Mode::Wanted {
+                    let existing_pages = existing_pages.as_ref().unwrap();
+                    pagelinks.retain(|page, _| !existing_pages.contains(page));
+                }
+
+                // Only keep existing pages without a link to the requested language edition
+                if cli.missing_language.is_some() {
+                    let existing_pages = existing_pages.as_ref().unwrap();
+                    let languages = languages.as_ref().unwrap();
+                    pagelinks.retain(|page, _| {
+                        existing_pages.contains(page)
+                            && !languages.get(page).is_some_and(|l| l.has_language)
+                    });
+                }
+
+                // Only keep pages within the target category tree
+                if let Some(target_members) = &target_members {
+                    pagelinks.retain(|page, _| target_members.contains(page));
+                }
+
+                // Only keep disambiguation pages, or only other pages, as requested
+                if cli.mode == Mode::Disambiguation || cli.exclude_disambiguation {
+                    let page_props = page_props.as_ref().unwrap();
+                    let keep = cli.mode == Mode::Disambiguation;
+                    pagelinks.retain(|page, _| {
+                        page_props.get(page).is_some_and(|p| p.disambiguation) == keep
+                    });
+                }
+
+                // Only keep existing pages not connected to a Wikidata item
+                if cli.missing_wikidata_item {
+                    let existing_pages = existing_pages.as_ref().unwrap();
+                    let page_props = page_props.as_ref().unwrap();
+                    pagelinks.retain(|page, _| {
+                        existing_pages.contains(page)
+                            && page_props
+                                .get(page)
+                                .is_none_or(|p| p.wikibase_item.is_none())
+                    });
+                }
+
+                // Only keep existing pages without edit protection
+                if cli.unprotected {
+                    let existing_pages = existing_pages.as_ref().unwrap();
+                    let protection = protection.as_ref().unwrap();
+                    pagelinks.retain(|page, _| {
+                        existing_pages.contains(page)
+                            && protection.get(page).is_none_or(|p| p.edit.is_none())
+                    });
+                }
+
+                // Reduce dataset to pages with link count above threshold, and sort in descending
+                // order (unless unsorted output is requested)
+                if cli.unsorted {
+                    // Rows are only produced as they are written out
+                    let pages = util::filter_pagelinks(pagelinks, cutoff);
+                    Rows::Unsorted(UnsortedResults::new(pages))
+                } else {
+                    Rows::Sorted(Results::new(util::sort_pagelinks(pagelinks, cutoff)))
+                }
+            }
+        };
+        eprintln!(" Done.");
+
+        if let Some(categories) = &category_sizes {
+            categorylinks_table::annotate(&mut columns, categories);
+        }
+        if let Some(rollups) = &category_rollups {
+            category_tree::annotate(&mut columns, rollups);
+        }
+        if let Some(categories) = &redirect_categories {
+            columns.annotate("Redirect categories", move |page, _| {
+                redirect_categories::to_value(categories.by_target.get(page))
+            });
+        }
+        if cli.mode == Mode::Stubs {
+            let sizes = &page_sizes;
+            columns.annotate("Bytes", move |page, _| Value::Int(u64::from(sizes[page])));
+            columns.annotate("Links per KB", move |page, links| {
+                Value::Float(maintenance::links_per_kb(links, sizes[page]))
+            });
         }
-    }?;
+        rows
+    };
 
-    // Reduce dataset to pages with link count above threshold, and sort in descending order
-    eprint!("4/5 Sorting pages (...)");
-    let pagelinks = util::sort_pagelinks(pagelinks, cli.cutoff_threshold);
-    eprintln!(" Done.");
+    if cli.mark_missing {
+        let existing_pages = existing_pages.as_ref().unwrap();
+        columns.annotate("Missing", move |page, _| {
+            Value::Bool(!existing_pages.contains(page))
+        });
+    }
+
+    if let Some(languages) = &languages {
+        columns.annotate("Languages", move |page, _| {
+            Value::Int(languages.get(page).map_or(0, |l| u64::from(l.editions)))
+        });
+    }
+
+    if let Some(page_props) = &page_props {
+        columns.annotate("Disambiguation", move |page, _| {
+            Value::Bool(page_props.get(page).is_some_and(|p| p.disambiguation))
+        });
+        columns.annotate("Wikidata item", move |page, _| {
+            match page_props.get(page).and_then(|p| p.wikibase_item.as_ref()) {
+                Some(item) => Value::Text(item.clone()),
+                None => Value::Empty,
+            }
+        });
+    }
+
+    if let Some(protection) = &protection {
+        columns.annotate("Edit protection", move |page, _| {
+            match protection.get(page).and_then(|p| p.edit.as_ref()) {
+                Some(restriction) => restriction.to_value(),
+                None => Value::Empty,
+            }
+        });
+        columns.annotate("Move protection", move |page, _| {
+            match protection.get(page).and_then(|p| p.moves.as_ref()) {
+                Some(restriction) => restriction.to_value(),
+                None => Value::Empty,
+            }
+        });
+    }
 
     // Write output
     eprint!("5/5 Writing results to {} (...)", output_file.1.display());
-    util::export_to_file(pagelinks, output_file.0, cli.export_format)?;
+    match rows {
+        Rows::Sorted(mut results) => {
+            results.add_columns(columns);
+            util::export_to_file(results, output_file.0, cli.export_format, &cli.run_info())?;
+        }
+        Rows::Unsorted(results) => {
+            util::export_unsorted_to_file(results.add_columns(columns), output_file.0)?
+        }
+    }
     eprintln!(" Done.");
 
     Ok(())
@@ -0,0 +1,108 @@
+/*
+Maintenance reports derived from the page index and link counts: orphaned pages (no incoming
+links), dead-end pages (no outgoing links) and highly linked stubs (short pages with many incoming
+links). Redirect pages are never reported.
+*/
+use crate::{
+    link_count::LinkCount,
+    page_table::PageIndex,
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+
+pub fn find_orphans(
+    index: &PageIndex,
+    pagelinks: &AHashMap<(PageNs, PageTitle), LinkCount>,
+    namespaces: &[PageNs],
+    via_redirects: bool,
+) -> Vec<((PageNs, PageTitle), LinkCount)> {
+    let mut orphans = Vec::new();
+
+    for page in index.values() {
+        if page.is_redirect || !namespaces.contains(&page.ns) {
+            continue;
+        }
+
+        let key = (page.ns, page.title.clone());
+        let link_count = pagelinks.get(&key).copied().unwrap_or_default();
+        let is_orphan = if via_redirects {
+            link_count.total() == 0
+        } else {
+            link_count.direct == 0
+        };
+
+        if is_orphan {
+            orphans.push((key, link_count));
+        }
+    }
+
+    sort_by_page(&mut orphans);
+    orphans
+}
+
+pub fn find_dead_ends(
+    index: &PageIndex,
+    outlinks: &AHashMap<PageId, LinkCount>,
+    namespaces: &[PageNs],
+) -> Vec<((PageNs, PageTitle), LinkCount)> {
+    let mut dead_ends: Vec<_> = index
+        .iter()
+        .filter(|(id, page)| {
+            !page.is_redirect && namespaces.contains(&page.ns) && !outlinks.contains_key(id)
+        })
+        .map(|(_, page)| ((page.ns, page.title.clone()), LinkCount::default()))
+        .collect();
+
+    sort_by_page(&mut dead_ends);
+    dead_ends
+}
+
+/*
+Pages of at most ‘max_bytes’ with at least ‘min_links’ incoming links, ordered by links per
+kilobyte of page size. Pages of unknown size (0 bytes) are left out.
+*/
+pub fn find_stubs(
+    index: &PageIndex,
+    pagelinks: &AHashMap<(PageNs, PageTitle), LinkCount>,
+    namespaces: &[PageNs],
+    max_bytes: u32,
+    min_links: u32,
+) -> Vec<((PageNs, PageTitle), LinkCount)> {
+    let mut stubs = Vec::new();
+
+    for page in index.values() {
+        if page.is_redirect
+            || !namespaces.contains(&page.ns)
+            || page.len == 0
+            || page.len > max_bytes
+        {
+            continue;
+        }
+
+        let key = (page.ns, page.title.clone());
+        if let Some(link_count) = pagelinks.get(&key) {
+            if link_count.total() >= min_links {
+                stubs.push((key, *link_count, links_per_kb(*link_count, page.len)));
+            }
+        }
+    }
+
+    // Pages of equal density by page, so that ranks are stable
+    stubs.sort_unstable_by(|a, b| b.2.partial_cmp(&a.2).unwrap().then_with(|| a.0.cmp(&b.0)));
+    stubs
+        .into_iter()
+        .map(|(page, link_count, _)| (page, link_count))
+        .collect()
+}
+
+pub fn links_per_kb(link_count: LinkCount, len: u32) -> f64 {
+    f64::from(link_count.total()) * 1024.0 / f64::from(len)
+}
+
+// Report pages by namespace, then title
+fn sort_by_page(pages: &mut [((PageNs, PageTitle), LinkCount)]) {
+    pages.sort_unstable_by(|((a_ns, a_title), _), ((b_ns, b_title), _)| {
+        (a_ns.0, &a_title.0).cmp(&(b_ns.0, &b_title.0))
+    });
+}
@@ -0,0 +1,59 @@
+/*
+Canonical (English) MediaWiki namespace names, used to translate title prefixes such as ‘Talk:’ into
+namespace numbers and back.
+*/
+use crate::util::PageNs;
+
+const NAMESPACES: &[(u32, &str)] = &[
+    (0, ""),
+    (1, "Talk"),
+    (2, "User"),
+    (3, "User talk"),
+    (4, "Project"),
+    (5, "Project talk"),
+    (6, "File"),
+    (7, "File talk"),
+    (8, "MediaWiki"),
+    (9, "MediaWiki talk"),
+    (10, "Template"),
+    (11, "Template talk"),
+    (12, "Help"),
+    (13, "Help talk"),
+    (14, "Category"),
+    (15, "Category talk"),
+    (100, "Portal"),
+    (101, "Portal talk"),
+    (118, "Draft"),
+    (119, "Draft talk"),
+    (710, "TimedText"),
+    (711, "TimedText talk"),
+    (828, "Module"),
+    (829, "Module talk"),
+];
+
+// Common aliases, including the Wikipedia-specific project namespace name
+const ALIASES: &[(u32, &str)] = &[
+    (4, "Wikipedia"),
+    (4, "WP"),
+    (5, "Wikipedia talk"),
+    (5, "WT"),
+    (6, "Image"),
+    (7, "Image talk"),
+];
+
+pub fn from_prefix(prefix: &str) -> Option<PageNs> {
+    let prefix = prefix.trim().replace('_', " ");
+
+    NAMESPACES
+        .iter()
+        .chain(ALIASES.iter())
+        .find(|(_, name)| !name.is_empty() && name.eq_ignore_ascii_case(&prefix))
+        .map(|(ns, _)| PageNs(*ns))
+}
+
+pub fn name(ns: PageNs) -> Option<&'static str> {
+    NAMESPACES
+        .iter()
+        .find(|(id, _)| *id == ns.0)
+        .map(|(_, name)| *name)
+}
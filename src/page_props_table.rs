@@ -0,0 +1,115 @@
+/*
+Process SQL dumps for the MediaWiki “page_props” table, i.e. find page properties such as whether a
+page is a disambiguation page, or which Wikidata item it is connected to.
+*/
+use crate::{
+    page_table::{self, PageIndex, PageRecord, PageRef},
+    sql_dump::RowParser,
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+#[derive(Clone, Default)]
+pub struct PageProps {
+    // Page carries the ‘disambiguation’ property
+    pub disambiguation: bool,
+    // Wikidata item ID (e.g. ‘Q42’), from the ‘wikibase_item’ property
+    pub wikibase_item: Option<String>,
+}
+
+pub type PagePropsMap = AHashMap<(PageNs, PageTitle), PageProps>;
+
+pub struct PropRow<'a> {
+    pub page: PageId,
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+impl PageRef for PropRow<'_> {
+    fn page(&self) -> PageId {
+        self.page
+    }
+}
+
+pub struct PagePropsParser {
+    regex: Regex,
+}
+
+impl RowParser for PagePropsParser {
+    type Row<'a> = PropRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<PropRow<'a>> {
+        Some(PropRow {
+            page: PageId(cap[1].parse::<u32>().unwrap()),
+            name: cap.get(2).unwrap().as_str(),
+            value: cap.get(3).unwrap().as_str(),
+        })
+    }
+}
+
+impl PageRecord<PropRow<'_>> for PageProps {
+    fn add(&mut self, row: PropRow) {
+        match row.name {
+            "disambiguation" => self.disambiguation = true,
+            "wikibase_item" => self.wikibase_item = Some(row.value.to_string()),
+            _ => (),
+        }
+    }
+
+    fn merge(&mut self, other: PageProps) {
+        self.disambiguation |= other.disambiguation;
+        if other.wikibase_item.is_some() {
+            self.wikibase_item = other.wikibase_item;
+        }
+    }
+}
+
+// Collect the properties of pages within the index
+pub fn collect_page_props<T>(
+    source: T,
+    index: &PageIndex,
+    buffer_size: usize,
+) -> Result<PagePropsMap>
+where
+    T: Read + Send,
+{
+    let parser = PagePropsParser {
+        regex: build_page_props_regex()?,
+    };
+
+    page_table::collect_records(
+        source,
+        &parser,
+        index,
+        "Extracting ‘page_props’ table data",
+        buffer_size,
+    )
+}
+
+/*
+Regex Pattern: \((\d+),'(disambiguation|wikibase_item)','((?:[^'\\]|\\.)*)',(?:[0-9.eE+-]+|NULL)\)
+
+(\d+) : match and capture the ‘pp_page’ field.
+
+'(disambiguation|wikibase_item)' : match and capture the ‘pp_propname’ field, for the properties
+of interest only. All other properties (e.g. ‘defaultsort’) are skipped.
+
+'((?:[^'\\]|\\.)*)' : match and capture the ‘pp_value’ field, which may contain any escaped
+character. For ‘wikibase_item’, this is the item ID.
+
+(?:[0-9.eE+-]+|NULL) : match the ‘pp_sortkey’ field, a floating point number or NULL.
+*/
+fn build_page_props_regex() -> Result<Regex> {
+    let pattern =
+        r"\((\d+),'(disambiguation|wikibase_item)','((?:[^'\\]|\\.)*)',(?:[0-9.eE+-]+|NULL)\)";
+    Regex::new(pattern).context("Building page_props regex")
+}
@@ -0,0 +1,248 @@
+/*
+Process SQL dumps for the MediaWiki “page_restrictions” table, i.e. find the edit and move
+protection of pages.
+*/
+use crate::{
+    page_table::{self, PageIndex, PageRecord, PageRef},
+    results::Value,
+    sql_dump::RowParser,
+    util::{PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::{
+    io::Read,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+pub struct Restriction {
+    // User group required, e.g. ‘autoconfirmed’ or ‘sysop’
+    pub level: String,
+    // Timestamp (e.g. ‘20251231235959’) or ‘infinity’
+    pub expiry: String,
+}
+
+impl Restriction {
+    // Level, with expiry unless indefinite, e.g. ‘sysop until 2025-12-31 23:59’
+    pub fn to_value(&self) -> Value {
+        let e = &self.expiry;
+        if is_timestamp(e) {
+            Value::Text(format!(
+                "{} until {}-{}-{} {}:{}",
+                self.level,
+                &e[..4],
+                &e[4..6],
+                &e[6..8],
+                &e[8..10],
+                &e[10..12]
+            ))
+        } else {
+            Value::Text(self.level.clone())
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Protection {
+    pub edit: Option<Restriction>,
+    pub moves: Option<Restriction>,
+}
+
+pub type PageProtection = AHashMap<(PageNs, PageTitle), Protection>;
+
+pub struct RestrictionRow<'a> {
+    pub page: PageId,
+    // Either ‘edit’ or ‘move’
+    pub action: &'a str,
+    pub level: &'a str,
+    pub expiry: &'a str,
+}
+
+impl PageRef for RestrictionRow<'_> {
+    fn page(&self) -> PageId {
+        self.page
+    }
+}
+
+pub struct PageRestrictionsParser {
+    regex: Regex,
+    // Current time as a timestamp, e.g. ‘20251231235959’, to skip expired restrictions
+    now: String,
+}
+
+impl PageRestrictionsParser {
+    fn new(now: String) -> Result<Self> {
+        Ok(Self {
+            regex: build_page_restrictions_regex()?,
+            now,
+        })
+    }
+}
+
+impl RowParser for PageRestrictionsParser {
+    type Row<'a> = RestrictionRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<RestrictionRow<'a>> {
+        let level = cap.get(3).unwrap().as_str();
+        // An empty level lifts the protection
+        if level.is_empty() {
+            return None;
+        }
+        // Expired restrictions may not have been purged yet (timestamps compare as text)
+        let expiry = cap.get(4).map_or("infinity", |m| m.as_str());
+        if is_timestamp(expiry) && expiry < self.now.as_str() {
+            return None;
+        }
+
+        Some(RestrictionRow {
+            page: PageId(cap[1].parse::<u32>().unwrap()),
+            action: cap.get(2).unwrap().as_str(),
+            level,
+            expiry,
+        })
+    }
+}
+
+impl PageRecord<RestrictionRow<'_>> for Protection {
+    fn add(&mut self, row: RestrictionRow) {
+        let restriction = Some(Restriction {
+            level: row.level.to_string(),
+            expiry: row.expiry.to_string(),
+        });
+        if row.action == "edit" {
+            self.edit = restriction;
+        } else {
+            self.moves = restriction;
+        }
+    }
+
+    fn merge(&mut self, other: Protection) {
+        if other.edit.is_some() {
+            self.edit = other.edit;
+        }
+        if other.moves.is_some() {
+            self.moves = other.moves;
+        }
+    }
+}
+
+fn is_timestamp(s: &str) -> bool {
+    s.len() == 14 && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+// Timestamp of the given number of seconds since the Unix epoch (UTC), e.g. ‘20251231235959’
+fn timestamp(secs: u64) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Civil date from the days since 1970-01-01, counting in 400-year eras starting in March
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// Collect the protection of pages within the index, other than expired restrictions
+pub fn collect_protection<T>(
+    source: T,
+    index: &PageIndex,
+    buffer_size: usize,
+) -> Result<PageProtection>
+where
+    T: Read + Send,
+{
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("Reading system time")?;
+    let parser = PageRestrictionsParser::new(timestamp(now.as_secs()))?;
+
+    page_table::collect_records(
+        source,
+        &parser,
+        index,
+        "Extracting ‘page_restrictions’ table data",
+        buffer_size,
+    )
+}
+
+/*
+Regex Pattern: \((\d+),'(edit|move)','([^']*)',[01],(?:NULL,|\d+,)?(?:'([^']*)'|NULL),\d+\)
+
+(\d+) : match and capture the ‘pr_page’ field.
+
+'(edit|move)' : match and capture the ‘pr_type’ field, for edit and move protection only. Other
+types (e.g. ‘upload’) are skipped.
+
+'([^']*)' : match and capture the ‘pr_level’ field, i.e. the user group allowed to perform the
+action (e.g. ‘sysop’).
+
+[01], : match the ‘pr_cascade’ field.
+
+(?:NULL,|\d+,)? : match the ‘pr_user’ field, which is unused and no longer part of newer dumps.
+
+(?:'([^']*)'|NULL) : match and capture the ‘pr_expiry’ field, either a timestamp or ‘infinity’. A
+NULL value, as found in very old rows, is taken to mean ‘infinity’.
+
+\d+ : match the ‘pr_id’ field.
+*/
+fn build_page_restrictions_regex() -> Result<Regex> {
+    let pattern = r"\((\d+),'(edit|move)','([^']*)',[01],(?:NULL,|\d+,)?(?:'([^']*)'|NULL),\d+\)";
+    Regex::new(pattern).context("Building page_restrictions regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0), "19700101000000");
+        assert_eq!(timestamp(951_825_600), "20000229120000");
+        assert_eq!(timestamp(1_767_225_599), "20251231235959");
+    }
+
+    #[test]
+    fn expired_restrictions_skipped() {
+        let parser = PageRestrictionsParser::new("20250101000000".to_string()).unwrap();
+        let expiry = |row: &str| {
+            let cap = parser.regex().captures(row).unwrap();
+            parser.parse(&cap).map(|r| r.expiry.to_string())
+        };
+
+        assert_eq!(expiry("(1,'edit','sysop',0,'20241231235959',1)"), None);
+        assert_eq!(
+            expiry("(1,'edit','sysop',0,'20250101000000',1)").as_deref(),
+            Some("20250101000000")
+        );
+        assert_eq!(
+            expiry("(1,'move','sysop',0,'infinity',1)").as_deref(),
+            Some("infinity")
+        );
+        assert_eq!(
+            expiry("(1,'edit','sysop',0,NULL,1)").as_deref(),
+            Some("infinity")
+        );
+    }
+}
@@ -2,82 +2,244 @@
 Process SQL dumps for the MediaWiki “page” table.
 */
 use crate::{
-    buffer_queue::BufferQueue,
-    chunked_reader::ChunkedReader,
-    progress_display::ProgressDisplay,
+    sql_dump::{self, Aggregator, RowParser},
     util::{self, PageId, PageNs, PageTitle},
 };
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{Context, Result};
-use regex::Regex;
+use regex::{Captures, Regex};
 
 use std::io::Read;
-use std::sync::Mutex;
+use std::marker::PhantomData;
+
+pub struct PageInfo {
+    pub ns: PageNs,
+    pub title: PageTitle,
+    pub is_redirect: bool,
+    // Size in bytes (‘page_len’), or 0 if not found in the dump
+    pub len: u32,
+}
+
+pub type PageIndex = AHashMap<PageId, PageInfo>;
+
+// Which pages, besides redirects, to include in the index
+pub enum IndexScope<'a> {
+    // Specific pages, within the redirect namespaces
+    Ids(&'a AHashSet<PageId>),
+    // All pages within the given namespaces
+    Namespaces(Vec<PageNs>),
+    // All pages
+    All,
+}
+
+impl IndexScope<'_> {
+    fn includes(&self, id: PageId, ns: PageNs) -> bool {
+        match self {
+            IndexScope::Ids(ids) => ids.contains(&id),
+            IndexScope::Namespaces(namespaces) => namespaces.contains(&ns),
+            IndexScope::All => true,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Pages {
+    // Redirect pages, to be mapped onto their targets
+    pub redirects: AHashMap<(PageNs, PageId), PageTitle>,
+    // Pages (redirect or not) within the index scope
+    pub index: PageIndex,
+}
+
+pub struct PageRow<'a> {
+    pub id: PageId,
+    pub ns: PageNs,
+    pub title: &'a str,
+    pub is_redirect: bool,
+    pub len: u32,
+}
+
+pub struct PageParser {
+    regex: Regex,
+}
+
+impl PageParser {
+    pub fn new(namespaces: Option<&[PageNs]>) -> Result<Self> {
+        Ok(Self {
+            regex: build_page_regex(namespaces)?,
+        })
+    }
+}
+
+impl RowParser for PageParser {
+    type Row<'a> = PageRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<PageRow<'a>> {
+        Some(PageRow {
+            id: PageId(cap[1].parse::<u32>().unwrap()),
+            ns: PageNs(cap[2].parse::<u32>().unwrap()),
+            title: cap.get(3).unwrap().as_str(),
+            is_redirect: &cap[4] == "1",
+            len: cap
+                .get(5)
+                .map_or(0, |len| len.as_str().parse::<u32>().unwrap()),
+        })
+    }
+}
+
+// Collect redirects within the given namespaces, and all pages within the index scope
+struct PageCollector<'a> {
+    redirect_namespaces: &'a [PageNs],
+    scope: &'a IndexScope<'a>,
+}
+
+impl<'r> Aggregator<PageRow<'r>> for PageCollector<'_> {
+    type Output = Pages;
+
+    fn add(&self, output: &mut Pages, row: PageRow<'r>) {
+        let maps_redirect = row.is_redirect && self.redirect_namespaces.contains(&row.ns);
+        let in_scope = self.scope.includes(row.id, row.ns);
+        if !maps_redirect && !in_scope {
+            return;
+        }
+
+        let title = PageTitle(row.title.to_string());
+
+        if in_scope {
+            let info = PageInfo {
+                ns: row.ns,
+                title: title.clone(),
+                is_redirect: row.is_redirect,
+                len: row.len,
+            };
+            output.index.insert(row.id, info);
+        }
+        if maps_redirect {
+            output.redirects.insert((row.ns, row.id), title);
+        }
+    }
+
+    fn merge(&self, output: &mut Pages, partial: Pages) {
+        output.redirects.extend(partial.redirects);
+        output.index.extend(partial.index);
+    }
+}
 
 pub fn collect_pages<T>(
     source: T,
-    namespaces: &[PageNs],
+    redirect_namespaces: &[PageNs],
+    scope: IndexScope,
     buffer_size: usize,
-) -> Result<AHashMap<(PageNs, PageId), PageTitle>>
+) -> Result<Pages>
 where
     T: Read + Send,
 {
-    let pages: Mutex<AHashMap<(PageNs, PageId), PageTitle>> = Mutex::new(AHashMap::new());
-
-    let mut source = ChunkedReader::new(source);
-    let buffers = BufferQueue::new(num_cpus::get() + 1, buffer_size);
-    let regex = build_page_regex(namespaces)?;
-
-    let mut progress = ProgressDisplay::new(buffer_size);
-
-    rayon::scope_fifo(|s| -> Result<()> {
-        let pages = &pages;
-        let regex = &regex;
-
-        loop {
-            eprint!(
-                "\r1/5 Extracting ‘page’ table data ({:.1} GiB processed)",
-                progress.next()
-            );
-            let buffer = buffers.pop();
-            let was_final_read = !source.read_into(&mut buffer.borrow(), buffer_size)?;
-
-            s.spawn_fifo(move |_| {
-                let mut new_pages = Vec::new();
-                for cap in regex.captures_iter(&buffer.borrow()) {
-                    let id = PageId(cap[1].parse::<u32>().unwrap());
-                    let ns = PageNs(cap[2].parse::<u32>().unwrap());
-                    let title = PageTitle(cap[3].to_string());
-
-                    new_pages.push(((ns, id), title));
+    let parser = match &scope {
+        IndexScope::All => PageParser::new(None)?,
+        IndexScope::Ids(_) => PageParser::new(Some(redirect_namespaces))?,
+        IndexScope::Namespaces(scope_namespaces) => {
+            let mut namespaces = redirect_namespaces.to_vec();
+            for ns in scope_namespaces {
+                if !namespaces.contains(ns) {
+                    namespaces.push(*ns);
                 }
+            }
+            PageParser::new(Some(&namespaces))?
+        }
+    };
+    let collector = PageCollector {
+        redirect_namespaces,
+        scope: &scope,
+    };
 
-                buffer.release();
+    sql_dump::process(
+        source,
+        &parser,
+        &collector,
+        "1/5 Extracting ‘page’ table data",
+        buffer_size,
+    )
+}
 
-                let mut pages = pages.lock().unwrap();
-                pages.extend(new_pages.into_iter());
-            });
+// A row of a table holding data about pages, which refers to them by ID (e.g. page_props)
+pub trait PageRef {
+    fn page(&self) -> PageId;
+}
 
-            if was_final_read {
-                break;
-            }
+// Data about a single page, built up from its rows
+pub trait PageRecord<R>: Default + Send {
+    fn add(&mut self, row: R);
+
+    fn merge(&mut self, other: Self);
+}
+
+// Collect a record for each page within the index, from the rows referring to it
+struct RecordCollector<'a, V> {
+    index: &'a PageIndex,
+    record: PhantomData<fn() -> V>,
+}
+
+impl<R, V> Aggregator<R> for RecordCollector<'_, V>
+where
+    R: PageRef,
+    V: PageRecord<R>,
+{
+    type Output = AHashMap<PageId, V>;
+
+    fn add(&self, output: &mut Self::Output, row: R) {
+        let page = row.page();
+        if self.index.contains_key(&page) {
+            output.entry(page).or_default().add(row);
         }
+    }
 
-        eprintln!(" Done.");
-        Ok(())
-    })?;
+    fn merge(&self, output: &mut Self::Output, partial: Self::Output) {
+        for (page, record) in partial {
+            output.entry(page).or_default().merge(record);
+        }
+    }
+}
 
-    Ok(pages.into_inner().unwrap())
+// Records of pages within the index, keyed by title
+pub fn collect_records<T, P, V>(
+    source: T,
+    parser: &P,
+    index: &PageIndex,
+    label: &str,
+    buffer_size: usize,
+) -> Result<AHashMap<(PageNs, PageTitle), V>>
+where
+    T: Read + Send,
+    P: RowParser,
+    for<'a> P::Row<'a>: PageRef,
+    V: for<'a> PageRecord<P::Row<'a>>,
+{
+    let collector = RecordCollector {
+        index,
+        record: PhantomData,
+    };
+    let records = sql_dump::process(source, parser, &collector, label, buffer_size)?;
+
+    Ok(records
+        .into_iter()
+        .map(|(id, record)| {
+            let page = &index[&id];
+            ((page.ns, page.title.clone()), record)
+        })
+        .collect())
 }
 
 /*
-Regex Pattern: \((\d+),({}),'((?:[^']|\\'){1,255}?)','[a-z:=]*?',1,
+Regex Pattern: \((\d+),({}),'((?:[^']|\\'){1,255}?)','[a-z:=]*?',([01]),(?:[01],[0-9.eE+-]+,'\d*',(?:'\d*'|NULL),\d+,(\d+),)?
 
 (\d+) : match and capture the ‘page_id’ field.
 
 ,({}), : match and capture ‘page_namespace’ on any of the given numbers (e.g. 0|5|7) passed via the
-function parameter.
+function parameter, or on any number if None.
 
 '((?:[^']|\\'){1,255}?)' : match and capture as ‘page_title’, any UTF-8 sequence of up to 255 bytes
 that does not contain ' except if escaped as \'. Strictly speaking, the 255 byte limit is not
@@ -86,13 +248,22 @@ needed, but it offers some protection against erroneous (long) matches in the ca
 '[a-z,:=]*?' : matches the ‘page_restrictions’ field. This field is deprecated, but matched here
 for the sake of completeness. Cost seems negligible.
 
-,1, : matches on the ‘page_is_redirect’ field being 1 (true), which we always want.
+,([01]), : match and capture the ‘page_is_redirect’ field. Only redirects are kept, unless the page
+falls within the requested index scope.
+
+(?:[01],[0-9.eE+-]+,'\d*',(?:'\d*'|NULL),\d+,(\d+),)? : match the ‘page_is_new’, ‘page_random’,
+‘page_touched’, ‘page_links_updated’ and ‘page_latest’ fields, then match and capture ‘page_len’.
+The group is optional, so that pages are still matched if these fields differ from the expected
+layout; their size is then unknown.
 */
-fn build_page_regex(namespaces: &[PageNs]) -> Result<Regex> {
-    let ns_str = util::namespaces_to_string(namespaces);
+fn build_page_regex(namespaces: Option<&[PageNs]>) -> Result<Regex> {
+    let ns_str = match namespaces {
+        Some(namespaces) => util::namespaces_to_string(namespaces),
+        None => r"\d+".to_string(),
+    };
 
     let pattern = format!(
-        r"\((\d+),({}),'((?:[^']|\\'){{1,255}}?)','[a-z,:=]*?',1,",
+        r"\((\d+),({}),'((?:[^']|\\'){{1,255}}?)','[a-z,:=]*?',([01]),(?:[01],[0-9.eE+-]+,'\d*',(?:'\d*'|NULL),\d+,(\d+),)?",
         ns_str
     );
     Regex::new(&pattern).context("Building page regex")
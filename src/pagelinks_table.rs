@@ -2,125 +2,502 @@
 Process SQL dumps for the MediaWiki “pagelinks” table.
 */
 use crate::{
-    buffer_queue::BufferQueue,
-    chunked_reader::ChunkedReader,
     link_count::LinkCount,
-    progress_display::ProgressDisplay,
-    util::{self, PageNs, PageTitle},
+    page_table::PageIndex,
+    redirect_table::RedirectMap,
+    sql_dump::{self, Aggregator, RowParser},
+    util::{self, PageId, PageNs, PageTitle},
 };
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet, RandomState};
 use anyhow::{Context, Result};
-use regex::Regex;
+use regex::{Captures, Regex};
 
+use std::hash::BuildHasher;
 use std::io::Read;
-use std::sync::Mutex;
 
-#[derive(Debug, Default)]
-pub struct Counter {
-    pub direct: u32,
-    pub indirect: u32,
+pub type PageLinks = AHashMap<(PageNs, PageTitle), LinkCount>;
+
+// Links via each redirect, along with the redirect's target
+pub type RedirectLinks = AHashMap<(PageNs, PageTitle), (PageTitle, u32)>;
+
+// Link rows to leave uncounted, based on their source page
+pub struct Exclusions<'a> {
+    // Index of (at least) all source pages
+    pub index: &'a PageIndex,
+    // Links leading back to their source page, directly or via a redirect
+    pub self_links: bool,
+    // Links originating from redirect pages
+    pub redirect_sources: bool,
+    // Links originating from pages other than these (e.g. those outside a category tree)
+    pub sources: Option<&'a AHashSet<PageId>>,
+}
+
+// What to count besides the link counts of all pages
+#[derive(Default)]
+pub struct CountOptions<'a> {
+    // Count only links to these pages (and to their redirects)
+    pub targets: Option<&'a AHashSet<(PageNs, PageTitle)>>,
+    pub exclusions: Option<&'a Exclusions<'a>>,
+    // Scan rows of all namespaces, so that the row counts include those filtered out
+    pub scan_all_rows: bool,
+    // Also count links per redirect they pass through
+    pub per_redirect: bool,
+}
+
+// Number of link rows seen, counted, or dropped by one of the exclusions
+#[derive(Clone, Copy, Default)]
+pub struct RowCounts {
+    pub scanned: u64,
+    pub counted: u64,
+    pub self_links: u64,
+    pub redirect_sources: u64,
+    pub other_sources: u64,
+}
+
+impl std::ops::AddAssign for RowCounts {
+    fn add_assign(&mut self, other: RowCounts) {
+        self.scanned += other.scanned;
+        self.counted += other.counted;
+        self.self_links += other.self_links;
+        self.redirect_sources += other.redirect_sources;
+        self.other_sources += other.other_sources;
+    }
+}
+
+impl Exclusions<'_> {
+    fn excludes(&self, from: PageId, ns: PageNs, target: &str, excluded: &mut RowCounts) -> bool {
+        if let Some(sources) = self.sources {
+            if !sources.contains(&from) {
+                excluded.other_sources += 1;
+                return true;
+            }
+        }
+        if let Some(source) = self.index.get(&from) {
+            if self.redirect_sources && source.is_redirect {
+                excluded.redirect_sources += 1;
+                return true;
+            }
+            if self.self_links && source.ns == ns && source.title.0 == target {
+                excluded.self_links += 1;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// A row of the pagelinks table, or of a table of the same shape (e.g. templatelinks)
+pub struct LinkRow<'a> {
+    pub from: PageId,
+    pub from_ns: PageNs,
+    pub ns: PageNs,
+    pub title: &'a str,
+}
+
+pub struct PagelinksParser {
+    regex: Regex,
+}
+
+impl PagelinksParser {
+    pub fn new(namespaces: Option<(&[PageNs], &[PageNs])>) -> Result<Self> {
+        Ok(Self {
+            regex: build_pagelinks_regex(namespaces)?,
+        })
+    }
+}
+
+impl RowParser for PagelinksParser {
+    type Row<'a> = LinkRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<LinkRow<'a>> {
+        Some(LinkRow {
+            from: PageId(cap[1].parse::<u32>().unwrap()),
+            from_ns: PageNs(cap[4].parse::<u32>().unwrap()),
+            ns: PageNs(cap[2].parse::<u32>().unwrap()),
+            title: cap.get(3).unwrap().as_str(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct LinkCounts {
+    pub links: PageLinks,
+    pub rows: RowCounts,
+    pub redirect_links: RedirectLinks,
+}
+
+// Count links per target, with links to redirects added to their target (as indirect)
+pub struct LinkCounter<'a> {
+    pub redirects: &'a RedirectMap,
+    pub targets: Option<&'a AHashSet<(PageNs, PageTitle)>>,
+    pub exclusions: Option<&'a Exclusions<'a>>,
+    // Namespaces to filter rows by, if not already done by the parser
+    pub namespaces: Option<(&'a [PageNs], &'a [PageNs])>,
+    // Also count links per redirect they pass through
+    pub per_redirect: bool,
+}
+
+impl<'r> Aggregator<LinkRow<'r>> for LinkCounter<'_> {
+    type Output = LinkCounts;
+
+    fn add(&self, output: &mut LinkCounts, row: LinkRow<'r>) {
+        output.rows.scanned += 1;
+
+        let LinkRow {
+            from,
+            from_ns,
+            ns,
+            title,
+        } = row;
+        if let Some(namespaces) = self.namespaces {
+            if !namespaces.0.contains(&from_ns) || !namespaces.1.contains(&ns) {
+                return;
+            }
+        }
+        let re_title = rd_query(self.redirects, ns, title);
+
+        if let Some(exclusions) = self.exclusions {
+            let target = re_title.map_or(title, |t| t.0.as_str());
+            if exclusions.excludes(from, ns, target, &mut output.rows) {
+                return;
+            }
+        }
+
+        if let Some(re_title) = re_title {
+            if let Some(link_count) = pl_query(&mut output.links, ns, &re_title.0) {
+                link_count.indirect += 1;
+            } else {
+                output
+                    .links
+                    .insert((ns, re_title.clone()), LinkCount::new(0, 1));
+            }
+            if self.per_redirect {
+                if let Some((_, count)) = pl_query(&mut output.redirect_links, ns, title) {
+                    *count += 1;
+                } else {
+                    output
+                        .redirect_links
+                        .insert((ns, PageTitle(title.to_string())), (re_title.clone(), 1));
+                }
+            }
+        } else { // Title is not a redirect
+            if let Some(targets) = self.targets {
+                if !target_query(targets, ns, title) {
+                    return;
+                }
+            }
+            if let Some(link_count) = pl_query(&mut output.links, ns, title) {
+                link_count.direct += 1;
+            } else {
+                output
+                    .links
+                    .insert((ns, PageTitle(title.to_string())), LinkCount::new(1, 0));
+            }
+        }
+        output.rows.counted += 1;
+    }
+
+    fn merge(&self, output: &mut LinkCounts, partial: LinkCounts) {
+        output.rows += partial.rows;
+        for (redirect, (target, count)) in partial.redirect_links {
+            output
+                .redirect_links
+                .entry(redirect)
+                .or_insert((target, 0))
+                .1 += count;
+        }
+        for (page, new_counter) in partial.links {
+            if let Some(counter) = output.links.get_mut(&page) {
+                *counter += new_counter;
+            } else {
+                output.links.insert(page, new_counter);
+            }
+        }
+    }
 }
 
 pub fn count_links<T>(
     source: T,
-    redirects: AHashMap<(PageNs, PageTitle), PageTitle>,
+    redirects: RedirectMap,
     namespaces: (&[PageNs], &[PageNs]),
+    options: &CountOptions,
     buffer_size: usize,
-) -> Result<AHashMap<(PageNs, PageTitle), LinkCount>>
+) -> Result<LinkCounts>
 where
     T: Read + Send,
 {
-    let pagelinks: Mutex<AHashMap<(PageNs, PageTitle), LinkCount>> = Mutex::new(AHashMap::new());
-
-    let mut source = ChunkedReader::new(source);
-    let buffers = BufferQueue::new(num_cpus::get() + 1, buffer_size);
-    let regex = build_pagelinks_regex(namespaces.0, namespaces.1)?;
-
-    let mut progress = ProgressDisplay::new(buffer_size);
-
-    rayon::scope_fifo(|s| -> Result<()> {
-        let pagelinks = &pagelinks;
-        let redirects = &redirects;
-        let regex = &regex;
-
-        loop {
-            eprint!(
-                "\r3/5 Extracting ‘pagelinks’ table data and counting links \
-                    ({:.1} GiB processed)",
-                progress.next()
-            );
-            let buffer = buffers.pop();
-            let was_final_read = !source.read_into(&mut buffer.borrow(), buffer_size)?;
-
-            s.spawn_fifo(move |_| {
-                let mut new_pagelinks = AHashMap::<(PageNs, PageTitle), LinkCount>::new();
-
-                for cap in regex.captures_iter(&buffer.borrow()) {
-                    let ns = PageNs(cap[1].parse::<u32>().unwrap());
-                    let title = &cap[2];
-
-                    if let Some(re_title) = rd_query(&redirects, ns, title) {
-                        if let Some(link_count) = pl_query(&mut new_pagelinks, ns, &re_title.0) {
-                            link_count.indirect += 1;
-                        } else {
-                            new_pagelinks.insert((ns, re_title.clone()), LinkCount::new(0, 1));
-                        }
-                    } else { // Title is not a redirect
-                        if let Some(link_count) = pl_query(&mut new_pagelinks, ns, title) {
-                            link_count.direct += 1;
-                        } else {
-                            new_pagelinks
-                                .insert((ns, PageTitle(title.to_string())), LinkCount::new(1, 0));
-                        }
-                    }
-                }
+    // To get a count of all rows, match any namespace and filter afterwards
+    let parser = if options.scan_all_rows {
+        PagelinksParser::new(None)?
+    } else {
+        PagelinksParser::new(Some(namespaces))?
+    };
+    let counter = LinkCounter {
+        redirects: &redirects,
+        targets: options.targets,
+        exclusions: options.exclusions,
+        namespaces: if options.scan_all_rows {
+            Some(namespaces)
+        } else {
+            None
+        },
+        per_redirect: options.per_redirect,
+    };
 
-                buffer.release();
+    sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘pagelinks’ table data and counting links",
+        buffer_size,
+    )
+}
 
-                let mut pagelinks = pagelinks.lock().unwrap();
-                for (page, new_counter) in new_pagelinks {
-                    if let Some(counter) = pagelinks.get_mut(&page) {
-                        *counter += new_counter;
-                    } else {
-                        pagelinks.insert(page, new_counter);
-                    }
-                }
-            });
+/*
+Count distinct targets of outgoing links per source page (by ‘pl_from’), split into links to regular
+pages (direct) and links to redirects (indirect). A page linking to a target both directly and via a
+redirect (or via several redirects) counts it once, as direct if any of its links is.
 
-            if was_final_read {
-                break;
+Only links via redirects, or to pages which redirects lead to, may share their target. These are
+kept track of by hash of the target, for one source page at a time, as the dump lists the links of
+each page together (ordered by ‘pl_from’). The pages at either end of a chunk may continue in
+another chunk, so their targets are kept until all chunks are merged.
+*/
+struct OutgoingCounter<'a> {
+    redirects: &'a RedirectMap,
+    // Hashes of the pages that redirects lead to
+    redirect_targets: AHashSet<u64>,
+    hasher: RandomState,
+    targets: Option<&'a AHashSet<(PageNs, PageTitle)>>,
+}
+
+// Hashes of the targets linked from a page, noting whether any link to each is direct
+type ResolvedTargets = AHashMap<u64, bool>;
+
+#[derive(Default)]
+struct OutgoingLinks {
+    counts: AHashMap<PageId, LinkCount>,
+    // Source pages which may continue in another chunk, with their targets
+    open: AHashMap<PageId, ResolvedTargets>,
+    // Source page currently being added, with its targets
+    current: Option<(PageId, ResolvedTargets)>,
+}
+
+impl OutgoingLinks {
+    fn start_source(&mut self, from: PageId) {
+        match self.current.take() {
+            // The first source page of a chunk
+            Some((page, targets)) if self.open.is_empty() => {
+                self.open.insert(page, targets);
             }
+            Some((page, targets)) => add_resolved(self.counts.entry(page).or_default(), &targets),
+            None => (),
         }
+        self.current = Some((from, ResolvedTargets::new()));
+    }
 
-        eprintln!(" Done.");
-        Ok(())
-    })?;
+    // Distinct targets per source page, once all chunks are merged
+    fn into_counts(self) -> AHashMap<PageId, LinkCount> {
+        let mut counts = self.counts;
+        for (page, targets) in self.open {
+            add_resolved(counts.entry(page).or_default(), &targets);
+        }
+        counts
+    }
+}
 
-    Ok(pagelinks.into_inner().unwrap())
+fn add_resolved(link_count: &mut LinkCount, targets: &ResolvedTargets) {
+    for direct in targets.values() {
+        if *direct {
+            link_count.direct += 1;
+        } else {
+            link_count.indirect += 1;
+        }
+    }
+}
+
+impl<'r> Aggregator<LinkRow<'r>> for OutgoingCounter<'_> {
+    type Output = OutgoingLinks;
+
+    fn add(&self, output: &mut OutgoingLinks, row: LinkRow<'r>) {
+        let re_title = rd_query(self.redirects, row.ns, row.title);
+        let target = re_title.map(|t| t.0.as_str()).unwrap_or(row.title);
+        if let Some(targets) = self.targets {
+            if !target_query(targets, row.ns, target) {
+                return;
+            }
+        }
+
+        if !matches!(&output.current, Some((from, _)) if *from == row.from) {
+            output.start_source(row.from);
+        }
+        let hash = self.hasher.hash_one((row.ns, target));
+        if re_title.is_some() || self.redirect_targets.contains(&hash) {
+            let (_, targets) = output.current.as_mut().unwrap();
+            *targets.entry(hash).or_default() |= re_title.is_none();
+        } else {
+            output.counts.entry(row.from).or_default().direct += 1;
+        }
+    }
+
+    fn merge(&self, output: &mut OutgoingLinks, mut partial: OutgoingLinks) {
+        // The last source page of the chunk
+        if let Some((page, targets)) = partial.current.take() {
+            partial.open.entry(page).or_default().extend(targets);
+        }
+
+        for (page, new_counter) in partial.counts {
+            *output.counts.entry(page).or_default() += new_counter;
+        }
+        for (page, new_targets) in partial.open {
+            let targets = output.open.entry(page).or_default();
+            for (target, new_direct) in new_targets {
+                *targets.entry(target).or_default() |= new_direct;
+            }
+        }
+    }
+}
+
+pub fn count_outgoing<T>(
+    source: T,
+    redirects: RedirectMap,
+    namespaces: (&[PageNs], &[PageNs]),
+    targets: Option<&AHashSet<(PageNs, PageTitle)>>,
+    buffer_size: usize,
+) -> Result<AHashMap<PageId, LinkCount>>
+where
+    T: Read + Send,
+{
+    let parser = PagelinksParser::new(Some(namespaces))?;
+    let hasher = RandomState::new();
+    let counter = OutgoingCounter {
+        redirects: &redirects,
+        redirect_targets: redirects
+            .iter()
+            .map(|((ns, _), target)| hasher.hash_one((*ns, target.0.as_str())))
+            .collect(),
+        hasher,
+        targets,
+    };
+
+    let links = sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘pagelinks’ table data and counting outgoing links",
+        buffer_size,
+    )?;
+    Ok(links.into_counts())
+}
+
+pub struct Backlink {
+    pub from: PageId,
+    pub target: (PageNs, PageTitle),
+    // Redirect through which the target is linked, if any
+    pub via: Option<PageTitle>,
+}
+
+/*
+Collect all links to the given targets, directly or via redirects. The redirect map is expected to
+be reduced to redirects leading to the targets.
+*/
+struct BacklinkCollector<'a> {
+    redirects: &'a RedirectMap,
+    targets: &'a AHashSet<(PageNs, PageTitle)>,
+}
+
+impl<'r> Aggregator<LinkRow<'r>> for BacklinkCollector<'_> {
+    type Output = Vec<Backlink>;
+
+    fn add(&self, output: &mut Vec<Backlink>, row: LinkRow<'r>) {
+        let LinkRow {
+            from, ns, title, ..
+        } = row;
+
+        let backlink = if let Some(re_title) = rd_query(self.redirects, ns, title) {
+            Backlink {
+                from,
+                target: (ns, re_title.clone()),
+                via: Some(PageTitle(title.to_string())),
+            }
+        } else if target_query(self.targets, ns, title) {
+            Backlink {
+                from,
+                target: (ns, PageTitle(title.to_string())),
+                via: None,
+            }
+        } else {
+            return;
+        };
+        output.push(backlink);
+    }
+
+    fn merge(&self, output: &mut Vec<Backlink>, partial: Vec<Backlink>) {
+        output.extend(partial);
+    }
+}
+
+pub fn find_backlinks<T>(
+    source: T,
+    redirects: RedirectMap,
+    namespaces: (&[PageNs], &[PageNs]),
+    targets: &AHashSet<(PageNs, PageTitle)>,
+    buffer_size: usize,
+) -> Result<Vec<Backlink>>
+where
+    T: Read + Send,
+{
+    let parser = PagelinksParser::new(Some(namespaces))?;
+    let collector = BacklinkCollector {
+        redirects: &redirects,
+        targets,
+    };
+
+    sql_dump::process(
+        source,
+        &parser,
+        &collector,
+        "3/5 Extracting ‘pagelinks’ table data and finding links to targets",
+        buffer_size,
+    )
 }
 
 /*
-Regex Pattern: \(\d+,({}),'((?:[^']|\\'){1,255}?)',(?:{})\)
+Regex Pattern: \((\d+),({}),'((?:[^']|\\'){1,255}?)',({})\)
 
-\d+ : match the ‘pl_from’ field.
+(\d+) : match and capture the ‘pl_from’ field.
 
 ,({}), : match and capture ‘pl_namespace’ on any of the given numbers (e.g. 0|5|7) passed via the
-second namespaces function parameter.
+second namespaces function parameter, or any number at all if no namespaces (or an empty list) are
+given.
 
 '((?:[^']|\\'){1,255}?)' : match and capture as ‘pl_title’, any UTF-8 sequence of up to 255 bytes
 that does not contain ' except if escaped as \'. Strictly speaking, the 255 byte limit is not
 needed, but it offers some protection against erroneous (long) matches in the case of faulty data.
 
-,(?:{}), : match the ‘pl_from_namespace’ on any of the numbers passed via the first namespaces
-function parameter.
+,({}), : match and capture the ‘pl_from_namespace’ on any of the numbers passed via the first
+namespaces function parameter, or again any number.
 */
-fn build_pagelinks_regex(namespaces_from: &[PageNs], namespaces_to: &[PageNs]) -> Result<Regex> {
-    let ns_from_str = util::namespaces_to_string(namespaces_from);
-    let ns_to_str = util::namespaces_to_string(namespaces_to);
+fn build_pagelinks_regex(namespaces: Option<(&[PageNs], &[PageNs])>) -> Result<Regex> {
+    let ns_str = |namespaces: &[PageNs]| {
+        if namespaces.is_empty() {
+            r"\d+".to_string()
+        } else {
+            util::namespaces_to_string(namespaces)
+        }
+    };
+    let (ns_from_str, ns_to_str) = match namespaces {
+        Some((from, to)) => (ns_str(from), ns_str(to)),
+        None => (r"\d+".to_string(), r"\d+".to_string()),
+    };
 
     let pattern = format!(
-        r"\(\d+,({}),'((?:[^']|\\'){{1,255}}?)',(?:{})\)",
+        r"\((\d+),({}),'((?:[^']|\\'){{1,255}}?)',({})\)",
         ns_to_str, ns_from_str
     );
     Regex::new(&pattern).context("Building pagelinks regex")
@@ -132,7 +509,7 @@ the lifetime of a query parameter is required to encompass that of the HashMap.
 temporary &str from the regex iterator, they would require cloning into String for each query.
 */
 #[inline]
-fn rd_query<'a>(
+pub fn rd_query<'a>(
     rd: &'a AHashMap<(PageNs, PageTitle), PageTitle>,
     ns: PageNs,
     title: &str,
@@ -153,11 +530,28 @@ fn rd_query<'a>(
 }
 
 #[inline]
-fn pl_query<'a>(
-    pl: &'a mut AHashMap<(PageNs, PageTitle), LinkCount>,
+pub fn target_query(targets: &AHashSet<(PageNs, PageTitle)>, ns: PageNs, title: &str) -> bool {
+    unsafe {
+        // As function above
+        let key = (
+            ns,
+            PageTitle(String::from_raw_parts(
+                title.as_ptr() as *mut u8,
+                title.len(),
+                title.len(),
+            )),
+        );
+        let key = std::mem::ManuallyDrop::new(key);
+        targets.contains(&*key)
+    }
+}
+
+#[inline]
+pub fn pl_query<'a, V>(
+    pl: &'a mut AHashMap<(PageNs, PageTitle), V>,
     ns: PageNs,
     title: &str,
-) -> Option<&'a mut LinkCount> {
+) -> Option<&'a mut V> {
     unsafe {
         // As function above
         let key = (
@@ -172,3 +566,156 @@ fn pl_query<'a>(
         pl.get_mut(&*key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page_table::PageInfo;
+
+    fn redirects() -> RedirectMap {
+        let title = |s: &str| PageTitle(s.to_string());
+        vec![
+            ((PageNs(0), title("Foo_alias")), title("Foo")),
+            ((PageNs(0), title("Foo_other")), title("Foo")),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    // Foo (1), its redirect Foo_alias (2), and two other pages linking to it (3 and 4)
+    const EXCLUSION_DUMP: &str = "INSERT INTO `pagelinks` VALUES (1,0,'Foo_alias',0),\
+        (1,0,'Foo',0),(2,0,'Bar',0),(3,0,'Foo',0),(4,0,'Foo_alias',0);\n";
+
+    fn index() -> PageIndex {
+        let page = |title: &str, is_redirect| PageInfo {
+            ns: PageNs(0),
+            title: PageTitle(title.to_string()),
+            is_redirect,
+            len: 0,
+        };
+        vec![
+            (PageId(1), page("Foo", false)),
+            (PageId(2), page("Foo_alias", true)),
+            (PageId(3), page("Bar", false)),
+            (PageId(4), page("Baz", false)),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn count_excluded(exclusions: &Exclusions) -> LinkCounts {
+        let namespaces: &[PageNs] = &[PageNs(0)];
+        count_links(
+            EXCLUSION_DUMP.as_bytes(),
+            redirects(),
+            (namespaces, namespaces),
+            &CountOptions {
+                exclusions: Some(exclusions),
+                ..CountOptions::default()
+            },
+            1024,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn exclusions_drop_self_links_and_redirect_sources() {
+        let index = index();
+        let counts = count_excluded(&Exclusions {
+            index: &index,
+            self_links: true,
+            redirect_sources: true,
+            sources: None,
+        });
+
+        // Both links from Foo lead back to it, one of them via its redirect
+        let rows = counts.rows;
+        assert_eq!((rows.scanned, rows.counted), (5, 2));
+        assert_eq!((rows.self_links, rows.redirect_sources), (2, 1));
+        assert_eq!(rows.other_sources, 0);
+        let foo = counts.links[&(PageNs(0), PageTitle("Foo".to_string()))];
+        assert_eq!((foo.direct, foo.indirect), (1, 1));
+        // Bar is only linked from the redirect
+        assert_eq!(counts.links.len(), 1);
+    }
+
+    #[test]
+    fn exclusions_by_source() {
+        let index = index();
+        let sources = vec![PageId(1), PageId(3)].into_iter().collect();
+        let counts = count_excluded(&Exclusions {
+            index: &index,
+            self_links: false,
+            redirect_sources: false,
+            sources: Some(&sources),
+        });
+
+        let rows = counts.rows;
+        assert_eq!((rows.scanned, rows.counted), (5, 3));
+        assert_eq!((rows.self_links, rows.redirect_sources), (0, 0));
+        assert_eq!(rows.other_sources, 2);
+        let foo = counts.links[&(PageNs(0), PageTitle("Foo".to_string()))];
+        assert_eq!((foo.direct, foo.indirect), (2, 1));
+        assert_eq!(counts.links.len(), 1);
+    }
+
+    #[test]
+    fn outgoing_target_counted_once() {
+        let dump = "INSERT INTO `pagelinks` VALUES (1,0,'Bar',0),(1,0,'Foo',0),\
+            (1,0,'Foo_alias',0),(1,0,'Foo_other',0),(2,0,'Foo_alias',0),(2,0,'Foo_other',0);\n";
+        let namespaces: &[PageNs] = &[PageNs(0)];
+        let counts = count_outgoing(
+            dump.as_bytes(),
+            redirects(),
+            (namespaces, namespaces),
+            None,
+            1024,
+        )
+        .unwrap();
+
+        let count = |id| (counts[&PageId(id)].direct, counts[&PageId(id)].indirect);
+        assert_eq!(count(1), (2, 0));
+        assert_eq!(count(2), (0, 1));
+    }
+
+    #[test]
+    fn outgoing_source_across_chunks() {
+        let redirects = redirects();
+        let hasher = RandomState::new();
+        let counter = OutgoingCounter {
+            redirects: &redirects,
+            redirect_targets: vec![hasher.hash_one((PageNs(0), "Foo"))]
+                .into_iter()
+                .collect(),
+            hasher,
+            targets: None,
+        };
+        let chunks: [&[(u32, &str)]; 3] = [
+            &[(1, "Bar"), (2, "Foo_alias")],
+            &[(2, "Foo_other")],
+            &[(2, "Foo"), (2, "Qux"), (3, "Foo_alias")],
+        ];
+
+        // Chunks may be merged in any order
+        let mut links = OutgoingLinks::default();
+        for chunk in chunks.iter().rev() {
+            let mut partial = OutgoingLinks::default();
+            for &(from, title) in *chunk {
+                let row = LinkRow {
+                    from: PageId(from),
+                    from_ns: PageNs(0),
+                    ns: PageNs(0),
+                    title,
+                };
+                counter.add(&mut partial, row);
+            }
+            counter.merge(&mut links, partial);
+        }
+        let counts = links.into_counts();
+
+        let count = |id| (counts[&PageId(id)].direct, counts[&PageId(id)].indirect);
+        assert_eq!(count(1), (1, 0));
+        assert_eq!(count(2), (2, 0));
+        assert_eq!(count(3), (0, 1));
+    }
+}
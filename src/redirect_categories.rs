@@ -0,0 +1,155 @@
+/*
+Break down links via redirects by the categories of those redirects (e.g. “Redirects from
+misspellings”), per target and in total. Links via a redirect in several categories count towards
+each of them; those via uncategorized redirects are listed separately.
+*/
+use crate::{
+    categorylinks_table::{CategoryRow, CategorylinksParser},
+    page_table::PageIndex,
+    pagelinks_table::RedirectLinks,
+    results::Value,
+    sql_dump::{self, Aggregator},
+    util::{self, PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashMap;
+use anyhow::Result;
+
+use std::io::Read;
+
+const UNCATEGORIZED: &str = "(uncategorized)";
+
+#[derive(Clone, Copy, Default)]
+pub struct CategoryTotal {
+    pub links: u64,
+    pub redirects: u32,
+}
+
+#[derive(Default)]
+pub struct RedirectCategories {
+    // Links via redirects to each target, per redirect category
+    pub by_target: AHashMap<(PageNs, PageTitle), AHashMap<PageTitle, u32>>,
+    pub totals: AHashMap<PageTitle, CategoryTotal>,
+}
+
+// Collect the categories of redirect pages within the index
+struct RedirectCategoryCollector<'a> {
+    index: &'a PageIndex,
+}
+
+impl<'r> Aggregator<CategoryRow<'r>> for RedirectCategoryCollector<'_> {
+    type Output = AHashMap<PageId, Vec<PageTitle>>;
+
+    fn add(&self, output: &mut Self::Output, row: CategoryRow<'r>) {
+        if self
+            .index
+            .get(&row.from)
+            .is_some_and(|page| page.is_redirect)
+        {
+            output
+                .entry(row.from)
+                .or_default()
+                .push(PageTitle(row.title.to_string()));
+        }
+    }
+
+    fn merge(&self, output: &mut Self::Output, partial: Self::Output) {
+        for (redirect, categories) in partial {
+            output.entry(redirect).or_default().extend(categories);
+        }
+    }
+}
+
+/*
+Split the links counted per redirect by the categories of the redirects. The index is expected to
+include all redirects within the ‘to’ namespaces.
+*/
+pub fn collect<T>(
+    source: T,
+    redirect_links: &RedirectLinks,
+    index: &PageIndex,
+    buffer_size: usize,
+) -> Result<RedirectCategories>
+where
+    T: Read + Send,
+{
+    let categories = sql_dump::process(
+        source,
+        &CategorylinksParser::new()?,
+        &RedirectCategoryCollector { index },
+        "Extracting ‘categorylinks’ table data (redirect categories)",
+        buffer_size,
+    )?;
+
+    // Categorylinks rows refer to redirects by ID, link counts by title
+    let categories: AHashMap<_, _> = categories
+        .into_iter()
+        .map(|(id, categories)| {
+            let page = &index[&id];
+            ((page.ns, page.title.clone()), categories)
+        })
+        .collect();
+    let uncategorized = vec![PageTitle(UNCATEGORIZED.to_string())];
+
+    let mut output = RedirectCategories::default();
+    for ((ns, redirect), (target, count)) in redirect_links {
+        let redirect_categories = categories
+            .get(&(*ns, redirect.clone()))
+            .unwrap_or(&uncategorized);
+        let by_category = output.by_target.entry((*ns, target.clone())).or_default();
+
+        for category in redirect_categories {
+            *by_category.entry(category.clone()).or_default() += count;
+            let total = output.totals.entry(category.clone()).or_default();
+            total.links += u64::from(*count);
+            total.redirects += 1;
+        }
+    }
+
+    Ok(output)
+}
+
+// Links per redirect category, in descending order, e.g. ‘Redirects from moves: 12, …’
+pub fn to_value(by_category: Option<&AHashMap<PageTitle, u32>>) -> Value {
+    let by_category = match by_category {
+        Some(by_category) => by_category,
+        None => return Value::Empty,
+    };
+
+    let mut categories: Vec<_> = by_category.iter().collect();
+    categories.sort_unstable_by(|a, b| (b.1, &a.0 .0).cmp(&(a.1, &b.0 .0)));
+    let categories: Vec<_> = categories
+        .into_iter()
+        .map(|(category, count)| {
+            format!(
+                "{}: {}",
+                util::underscores_to_spaces(util::sql_unescape(&category.0)),
+                count
+            )
+        })
+        .collect();
+    Value::Text(categories.join(", "))
+}
+
+// One row per redirect category with at least ‘cutoff’ links, in descending order
+pub fn sort_totals(
+    totals: AHashMap<PageTitle, CategoryTotal>,
+    cutoff: u32,
+) -> Vec<(String, Vec<Value>)> {
+    let mut totals: Vec<_> = totals
+        .into_iter()
+        .filter(|(_, total)| total.links >= u64::from(cutoff))
+        .collect();
+    totals.sort_unstable_by(|a, b| (b.1.links, &a.0 .0).cmp(&(a.1.links, &b.0 .0)));
+
+    totals
+        .into_iter()
+        .map(|(category, total)| {
+            let values = vec![
+                Value::Int(total.links),
+                Value::Int(u64::from(total.redirects)),
+            ];
+            (util::underscores_to_spaces(category.0), values)
+        })
+        .collect()
+}
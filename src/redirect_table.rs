@@ -2,82 +2,105 @@
 Process SQL dumps for the MediaWiki “redirect” table.
 */
 use crate::{
-    buffer_queue::BufferQueue,
-    chunked_reader::ChunkedReader,
-    progress_display::ProgressDisplay,
+    sql_dump::{self, Aggregator, RowParser},
     util::{self, PageId, PageNs, PageTitle},
 };
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{Context, Result};
-use regex::Regex;
+use regex::{Captures, Regex};
 
 use std::io::Read;
-use std::sync::Mutex;
+
+pub type RedirectMap = AHashMap<(PageNs, PageTitle), PageTitle>;
+
+pub struct RedirectRow<'a> {
+    pub from: PageId,
+    pub ns: PageNs,
+    pub title: &'a str,
+}
+
+pub struct RedirectParser {
+    regex: Regex,
+}
+
+impl RedirectParser {
+    pub fn new(namespaces: &[PageNs]) -> Result<Self> {
+        Ok(Self {
+            regex: build_redirect_regex(namespaces)?,
+        })
+    }
+}
+
+impl RowParser for RedirectParser {
+    type Row<'a> = RedirectRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<RedirectRow<'a>> {
+        Some(RedirectRow {
+            from: PageId(cap[1].parse::<u32>().unwrap()),
+            ns: PageNs(cap[2].parse::<u32>().unwrap()),
+            title: cap.get(3).unwrap().as_str(),
+        })
+    }
+}
+
+// Map redirect pages (by title) onto their target titles
+struct RedirectMapper<'a> {
+    pages: &'a AHashMap<(PageNs, PageId), PageTitle>,
+    targets: Option<&'a AHashSet<(PageNs, PageTitle)>>,
+}
+
+impl<'r> Aggregator<RedirectRow<'r>> for RedirectMapper<'_> {
+    type Output = RedirectMap;
+
+    fn add(&self, output: &mut RedirectMap, row: RedirectRow<'r>) {
+        if let Some(source_title) = self.pages.get(&(row.ns, row.from)) {
+            let source = (row.ns, source_title.clone());
+            let target = (row.ns, PageTitle(row.title.to_string()));
+
+            // Only keep redirects that may lead to (or are) one of the targets
+            if let Some(targets) = self.targets {
+                if !targets.contains(&target) && !targets.contains(&source) {
+                    return;
+                }
+            }
+
+            output.insert(source, target.1);
+        }
+    }
+
+    fn merge(&self, output: &mut RedirectMap, partial: RedirectMap) {
+        output.extend(partial);
+    }
+}
 
 pub fn map_redirects<T>(
     source: T,
     pages: AHashMap<(PageNs, PageId), PageTitle>,
     namespaces: &[PageNs],
+    targets: Option<&AHashSet<(PageNs, PageTitle)>>,
     buffer_size: usize,
-) -> Result<AHashMap<(PageNs, PageTitle), PageTitle>>
+) -> Result<RedirectMap>
 where
     T: Read + Send,
 {
-    let redirects: Mutex<AHashMap<(PageNs, PageTitle), PageTitle>> = Mutex::new(AHashMap::new());
-
-    let mut source = ChunkedReader::new(source);
-    let buffers = BufferQueue::new(num_cpus::get() + 1, buffer_size);
-    let regex = build_redirect_regex(namespaces)?;
-
-    let mut progress = ProgressDisplay::new(buffer_size);
-
-    rayon::scope_fifo(|s| -> Result<()> {
-        let pages = &pages;
-        let redirects = &redirects;
-        let regex = &regex;
-
-        loop {
-            eprint!(
-                "\r2/5 Extracting ‘redirect’ table data and mapping relations \
-                ({:.1} GiB processed)",
-                progress.next()
-            );
-            let buffer = buffers.pop();
-            let was_final_read = !source.read_into(&mut buffer.borrow(), buffer_size)?;
-
-            s.spawn_fifo(move |_| {
-                let mut new_redirects: Vec<((PageNs, PageTitle), PageTitle)> = Vec::new();
-
-                for cap in regex.captures_iter(&buffer.borrow()) {
-                    let source_id = PageId(cap[1].parse::<u32>().unwrap());
-                    let source_ns = PageNs(cap[2].parse::<u32>().unwrap());
-                    let target_title = &cap[3];
-
-                    if let Some(source_title) = pages.get(&(source_ns, source_id)) {
-                        new_redirects.push((
-                            (source_ns, source_title.clone()),
-                            PageTitle(target_title.to_string()),
-                        ));
-                    }
-                }
-
-                buffer.release();
-
-                let mut redirects = redirects.lock().unwrap();
-                redirects.extend(new_redirects.into_iter());
-            });
-
-            if was_final_read {
-                break;
-            }
-        }
-
-        eprintln!(" Done.");
-        Ok(())
-    })?;
-
-    Ok(redirects.into_inner().unwrap())
+    let parser = RedirectParser::new(namespaces)?;
+    let mapper = RedirectMapper {
+        pages: &pages,
+        targets,
+    };
+
+    sql_dump::process(
+        source,
+        &parser,
+        &mapper,
+        "2/5 Extracting ‘redirect’ table data and mapping relations",
+        buffer_size,
+    )
 }
 
 /*
@@ -0,0 +1,246 @@
+/*
+Final, sorted output rows. Besides their link count, rows may carry any number of additional
+columns (e.g. whether the page exists), which are added after sorting. Rows not about pages (e.g.
+domains) are keyed by name instead, with all their counts given as additional columns. Unsorted
+rows are only produced as they are written out, additional columns included.
+*/
+use crate::{
+    link_count::LinkCount,
+    page_table::PageIndex,
+    pagelinks_table::Backlink,
+    util::{self, PageNs, PageTitle},
+};
+
+use std::fmt;
+
+pub enum Value {
+    Bool(bool),
+    Int(u64),
+    Float(f64),
+    Text(String),
+    Empty,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(true) => write!(f, "yes"),
+            Value::Bool(false) => write!(f, "no"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:.2}", x),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Empty => Ok(()),
+        }
+    }
+}
+
+pub enum Key {
+    Page((PageNs, PageTitle)),
+    Name(String),
+}
+
+pub struct Entry {
+    pub key: Key,
+    pub links: LinkCount,
+    pub extra: Vec<Value>,
+}
+
+pub struct Results {
+    // Heading of the first column, if rows are keyed by name
+    pub name_heading: Option<&'static str>,
+    pub columns: Vec<&'static str>,
+    pub entries: Vec<Entry>,
+}
+
+impl Results {
+    pub fn new(pages: Vec<((PageNs, PageTitle), LinkCount)>) -> Self {
+        let entries = pages
+            .into_iter()
+            .map(|(page, links)| Entry {
+                key: Key::Page(page),
+                links,
+                extra: Vec::new(),
+            })
+            .collect();
+
+        Self {
+            name_heading: None,
+            columns: Vec::new(),
+            entries,
+        }
+    }
+
+    // Rows keyed by name, e.g. a domain, with the given values for each column
+    pub fn from_names(
+        heading: &'static str,
+        columns: Vec<&'static str>,
+        rows: Vec<(String, Vec<Value>)>,
+    ) -> Self {
+        let entries = rows
+            .into_iter()
+            .map(|(name, extra)| Entry {
+                key: Key::Name(name),
+                links: LinkCount::default(),
+                extra,
+            })
+            .collect();
+
+        Self {
+            name_heading: Some(heading),
+            columns,
+            entries,
+        }
+    }
+
+    /*
+    One row per link (source page, with its ID) to a target, noting the redirect it passes through,
+    if any. Rows are ordered by target, then direct links before those via redirects, then source.
+    */
+    pub fn from_backlinks(mut backlinks: Vec<Backlink>, index: &PageIndex) -> Self {
+        backlinks.sort_unstable_by(|a, b| backlink_order(a).cmp(&backlink_order(b)));
+
+        let entries = backlinks
+            .into_iter()
+            .filter_map(|link| {
+                let source = index.get(&link.from)?;
+                let links = match link.via {
+                    Some(_) => LinkCount::new(0, 1),
+                    None => LinkCount::new(1, 0),
+                };
+                let via = match &link.via {
+                    Some(via) => Value::Text(util::display_title(link.target.0, via)),
+                    None => Value::Empty,
+                };
+
+                Some(Entry {
+                    key: Key::Page((source.ns, source.title.clone())),
+                    links,
+                    extra: vec![
+                        Value::Int(u64::from(link.from.0)),
+                        Value::Text(util::display_title(link.target.0, &link.target.1)),
+                        via,
+                    ],
+                })
+            })
+            .collect();
+
+        Self {
+            name_heading: None,
+            columns: vec!["Source ID", "Target", "Via redirect"],
+            entries,
+        }
+    }
+
+    // Add a column, with its value for each page row determined by the given function
+    pub fn annotate<F>(&mut self, column: &'static str, value: F)
+    where
+        F: Fn(&(PageNs, PageTitle), LinkCount) -> Value,
+    {
+        self.columns.push(column);
+        for entry in &mut self.entries {
+            let new_value = match &entry.key {
+                Key::Page(page) => value(page, entry.links),
+                Key::Name(_) => Value::Empty,
+            };
+            entry.extra.push(new_value);
+        }
+    }
+
+    pub fn add_columns(&mut self, columns: Columns) {
+        for (column, value) in columns.0 {
+            self.annotate(column, value);
+        }
+    }
+}
+
+type ColumnValue<'a> = Box<dyn Fn(&(PageNs, PageTitle), LinkCount) -> Value + 'a>;
+
+// Additional columns, to be added to the rows once these are final
+#[derive(Default)]
+pub struct Columns<'a>(Vec<(&'static str, ColumnValue<'a>)>);
+
+impl<'a> Columns<'a> {
+    // Add a column, with its value for each page row determined by the given function
+    pub fn annotate<F>(&mut self, column: &'static str, value: F)
+    where
+        F: Fn(&(PageNs, PageTitle), LinkCount) -> Value + 'a,
+    {
+        self.0.push((column, Box::new(value)));
+    }
+}
+
+// Rows in no particular order, produced one by one as they are written out
+pub struct UnsortedResults<'a> {
+    pub columns: Vec<&'static str>,
+    pub entries: Box<dyn Iterator<Item = Entry> + 'a>,
+}
+
+impl<'a> UnsortedResults<'a> {
+    pub fn new<I>(pages: I) -> Self
+    where
+        I: Iterator<Item = ((PageNs, PageTitle), LinkCount)> + 'a,
+    {
+        let entries = pages.map(|(page, links)| Entry {
+            key: Key::Page(page),
+            links,
+            extra: Vec::new(),
+        });
+
+        Self {
+            columns: Vec::new(),
+            entries: Box::new(entries),
+        }
+    }
+
+    // Rows keyed by name, with the given values for each column
+    pub fn from_names<I>(columns: Vec<&'static str>, rows: I) -> Self
+    where
+        I: Iterator<Item = (String, Vec<Value>)> + 'a,
+    {
+        let entries = rows.map(|(name, extra)| Entry {
+            key: Key::Name(name),
+            links: LinkCount::default(),
+            extra,
+        });
+
+        Self {
+            columns,
+            entries: Box::new(entries),
+        }
+    }
+
+    pub fn add_columns(self, columns: Columns<'a>) -> Self {
+        let mut headings = self.columns;
+        headings.extend(columns.0.iter().map(|(column, _)| *column));
+
+        let entries = self.entries.map(move |mut entry| {
+            for (_, value) in &columns.0 {
+                entry.extra.push(match &entry.key {
+                    Key::Page(page) => value(page, entry.links),
+                    Key::Name(_) => Value::Empty,
+                });
+            }
+            entry
+        });
+
+        Self {
+            columns: headings,
+            entries: Box::new(entries),
+        }
+    }
+}
+
+// Output rows, either sorted, or unsorted (see --unsorted)
+pub enum Rows<'a> {
+    Sorted(Results),
+    Unsorted(UnsortedResults<'a>),
+}
+
+fn backlink_order(link: &Backlink) -> (u32, &str, Option<&str>, u32) {
+    (
+        (link.target.0).0,
+        &(link.target.1).0,
+        link.via.as_ref().map(|via| via.0.as_str()),
+        link.from.0,
+    )
+}
@@ -0,0 +1,91 @@
+/*
+Generic processing of MediaWiki SQL table dumps. The dump is read in chunks, which are handled in
+parallel: a RowParser extracts rows from the text via its regex, and an Aggregator adds them to a
+partial result per chunk, which is then merged into the final result.
+*/
+use crate::{
+    buffer_queue::BufferQueue, chunked_reader::ChunkedReader, progress_display::ProgressDisplay,
+};
+
+use anyhow::Result;
+use regex::{Captures, Regex};
+
+use std::io::Read;
+use std::sync::Mutex;
+
+pub trait RowParser: Sync {
+    // A row may borrow from the dump text, as well as from the parser itself
+    type Row<'a>
+    where
+        Self: 'a;
+
+    fn regex(&self) -> &Regex;
+
+    // Rows may be skipped by returning None
+    fn parse<'a>(&'a self, captures: &Captures<'a>) -> Option<Self::Row<'a>>;
+}
+
+pub trait Aggregator<R>: Sync {
+    type Output: Default + Send;
+
+    fn add(&self, output: &mut Self::Output, row: R);
+
+    fn merge(&self, output: &mut Self::Output, partial: Self::Output);
+}
+
+/*
+Run parser and aggregator over the whole dump, displaying the given label along with the progress.
+*/
+pub fn process<T, P, A, O>(
+    source: T,
+    parser: &P,
+    aggregator: &A,
+    label: &str,
+    buffer_size: usize,
+) -> Result<O>
+where
+    T: Read + Send,
+    P: RowParser,
+    A: for<'a> Aggregator<P::Row<'a>, Output = O>,
+    O: Default + Send,
+{
+    let output: Mutex<O> = Mutex::new(O::default());
+
+    let mut source = ChunkedReader::new(source);
+    let buffers = BufferQueue::new(num_cpus::get() + 1, buffer_size);
+
+    let mut progress = ProgressDisplay::new(buffer_size);
+
+    rayon::scope_fifo(|s| -> Result<()> {
+        let output = &output;
+
+        loop {
+            eprint!("\r{} ({:.1} GiB processed)", label, progress.next());
+            let buffer = buffers.pop();
+            let was_final_read = !source.read_into(&mut buffer.borrow(), buffer_size)?;
+
+            s.spawn_fifo(move |_| {
+                let mut partial = O::default();
+
+                for cap in parser.regex().captures_iter(&buffer.borrow()) {
+                    if let Some(row) = parser.parse(&cap) {
+                        aggregator.add(&mut partial, row);
+                    }
+                }
+
+                buffer.release();
+
+                aggregator.merge(&mut output.lock().unwrap(), partial);
+            });
+
+            if was_final_read {
+                break;
+            }
+        }
+
+        eprintln!(" Done.");
+        Ok(())
+    })?;
+
+    Ok(output.into_inner().unwrap())
+}
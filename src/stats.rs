@@ -0,0 +1,277 @@
+/*
+Summary statistics of the counted link graph: row counts, share of links via redirects, and the
+distribution of in-degrees (links per target page) as quantiles, log-binned histogram and Gini
+coefficient.
+*/
+use crate::pagelinks_table::{PageLinks, RowCounts};
+
+use anyhow::{Context, Result};
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+const QUANTILES: &[(&str, f64)] = &[
+    ("p25", 0.25),
+    ("median", 0.5),
+    ("p75", 0.75),
+    ("p90", 0.9),
+    ("p99", 0.99),
+    ("p99.9", 0.999),
+];
+
+pub struct LinkStats {
+    rows: RowCounts,
+    redirects: usize,
+    targets: usize,
+    links_total: u64,
+    links_indirect: u64,
+    min: u32,
+    max: u32,
+    mean: f64,
+    quantiles: Vec<(&'static str, u32)>,
+    // Number of targets with in-degree 0, then per range [2^(i-1), 2^i) for bin i > 0
+    histogram: Vec<u64>,
+    gini: f64,
+}
+
+impl LinkStats {
+    pub fn new(pagelinks: &PageLinks, rows: RowCounts, redirects: usize) -> Self {
+        let mut degrees: Vec<u32> = pagelinks.values().map(|lc| lc.total()).collect();
+        degrees.sort_unstable();
+
+        let links_total: u64 = degrees.iter().map(|&d| u64::from(d)).sum();
+        let links_indirect: u64 = pagelinks.values().map(|lc| u64::from(lc.indirect)).sum();
+
+        let quantiles = QUANTILES
+            .iter()
+            .map(|&(name, q)| (name, quantile(&degrees, q)))
+            .collect();
+
+        let mut histogram = vec![0u64];
+        for &degree in &degrees {
+            let bin = if degree == 0 {
+                0
+            } else {
+                (32 - degree.leading_zeros()) as usize
+            };
+            if histogram.len() <= bin {
+                histogram.resize(bin + 1, 0);
+            }
+            histogram[bin] += 1;
+        }
+
+        Self {
+            rows,
+            redirects,
+            targets: degrees.len(),
+            links_total,
+            links_indirect,
+            min: degrees.first().copied().unwrap_or(0),
+            max: degrees.last().copied().unwrap_or(0),
+            mean: ratio(links_total, degrees.len() as u64),
+            quantiles,
+            histogram,
+            gini: gini(&degrees, links_total),
+        }
+    }
+
+    // Plain text, or JSON if the file name ends in ‘.json’
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)
+            .with_context(|| format!("Failed to create stats file ‘{}’", path.display()))?;
+
+        match path.extension() {
+            Some(ext) if ext == "json" => self.write_json(&mut file),
+            _ => self.write_plaintext(&mut file),
+        }
+    }
+
+    fn write_plaintext(&self, file: &mut File) -> Result<()> {
+        writeln!(file, "Link rows scanned:           {}", self.rows.scanned)?;
+        writeln!(file, "Link rows counted:           {}", self.rows.counted)?;
+        writeln!(
+            file,
+            "Excluded self-links:         {}",
+            self.rows.self_links
+        )?;
+        writeln!(
+            file,
+            "Excluded from redirects:     {}",
+            self.rows.redirect_sources
+        )?;
+        writeln!(
+            file,
+            "Excluded by source category: {}",
+            self.rows.other_sources
+        )?;
+        writeln!(file, "Redirects mapped:            {}", self.redirects)?;
+        writeln!(file, "Distinct targets:            {}", self.targets)?;
+        writeln!(file, "Links total:                 {}", self.links_total)?;
+        writeln!(
+            file,
+            "Links via redirect:          {} ({:.2}%)",
+            self.links_indirect,
+            100.0 * ratio(self.links_indirect, self.links_total)
+        )?;
+
+        writeln!(file, "\nIn-degree distribution\n")?;
+        writeln!(file, "min       {}", self.min)?;
+        for (name, value) in &self.quantiles {
+            writeln!(file, "{:<9} {}", name, value)?;
+        }
+        writeln!(file, "max       {}", self.max)?;
+        writeln!(file, "mean      {:.2}", self.mean)?;
+        writeln!(file, "Gini      {:.4}", self.gini)?;
+
+        writeln!(file, "\nIn-degree histogram (targets per range)\n")?;
+        for (i, count) in self.histogram.iter().enumerate() {
+            let (low, high) = bin_range(i);
+            writeln!(file, "{:>10} – {:<10} {}", low, high, count)?;
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, file: &mut File) -> Result<()> {
+        writeln!(file, "{{")?;
+        writeln!(file, "  \"rows_scanned\": {},", self.rows.scanned)?;
+        writeln!(file, "  \"rows_counted\": {},", self.rows.counted)?;
+        writeln!(file, "  \"excluded_self_links\": {},", self.rows.self_links)?;
+        writeln!(
+            file,
+            "  \"excluded_redirect_sources\": {},",
+            self.rows.redirect_sources
+        )?;
+        writeln!(
+            file,
+            "  \"excluded_source_category\": {},",
+            self.rows.other_sources
+        )?;
+        writeln!(file, "  \"redirects\": {},", self.redirects)?;
+        writeln!(file, "  \"distinct_targets\": {},", self.targets)?;
+        writeln!(file, "  \"links_total\": {},", self.links_total)?;
+        writeln!(file, "  \"links_indirect\": {},", self.links_indirect)?;
+        writeln!(
+            file,
+            "  \"indirect_share\": {:.6},",
+            ratio(self.links_indirect, self.links_total)
+        )?;
+
+        writeln!(file, "  \"in_degree\": {{")?;
+        writeln!(file, "    \"min\": {},", self.min)?;
+        for (name, value) in &self.quantiles {
+            writeln!(file, "    \"{}\": {},", name, value)?;
+        }
+        writeln!(file, "    \"max\": {},", self.max)?;
+        writeln!(file, "    \"mean\": {:.6},", self.mean)?;
+        writeln!(file, "    \"gini\": {:.6}", self.gini)?;
+        writeln!(file, "  }},")?;
+
+        writeln!(file, "  \"histogram\": [")?;
+        for (i, count) in self.histogram.iter().enumerate() {
+            let (low, high) = bin_range(i);
+            let separator = if i + 1 < self.histogram.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(
+                file,
+                "    {{ \"min\": {}, \"max\": {}, \"targets\": {} }}{}",
+                low, high, count, separator
+            )?;
+        }
+        writeln!(file, "  ]")?;
+        writeln!(file, "}}")?;
+        Ok(())
+    }
+}
+
+// Nearest-rank quantile of sorted values
+fn quantile(sorted: &[u32], q: f64) -> u32 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Gini coefficient of sorted values: 0 for perfect equality, approaching 1 for maximum inequality
+fn gini(sorted: &[u32], sum: u64) -> f64 {
+    if sorted.is_empty() || sum == 0 {
+        return 0.0;
+    }
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| (i + 1) as f64 * f64::from(x))
+        .sum();
+
+    (2.0 * weighted) / (n * sum as f64) - (n + 1.0) / n
+}
+
+fn bin_range(bin: usize) -> (u64, u64) {
+    if bin == 0 {
+        (0, 0)
+    } else {
+        (1 << (bin - 1), (1 << bin) - 1)
+    }
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 {
+        0.0
+    } else {
+        a as f64 / b as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_empty_and_single() {
+        assert_eq!(quantile(&[], 0.5), 0);
+        assert_eq!(quantile(&[7], 0.25), 7);
+        assert_eq!(quantile(&[7], 0.999), 7);
+    }
+
+    #[test]
+    fn quantile_nearest_rank() {
+        let sorted = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert_eq!(quantile(&sorted, 0.25), 3);
+        assert_eq!(quantile(&sorted, 0.5), 5);
+        assert_eq!(quantile(&sorted, 0.999), 10);
+    }
+
+    #[test]
+    fn gini_empty_and_single() {
+        assert_eq!(gini(&[], 0), 0.0);
+        assert_eq!(gini(&[5], 5), 0.0);
+        assert_eq!(gini(&[0], 0), 0.0);
+    }
+
+    #[test]
+    fn gini_equality_and_inequality() {
+        assert!(gini(&[3, 3, 3, 3], 12).abs() < 1e-9);
+        // One target holding all links out of four: (n - 1) / n
+        assert!((gini(&[0, 0, 0, 8], 8) - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stats_without_links() {
+        let stats = LinkStats::new(&PageLinks::default(), RowCounts::default(), 0);
+        assert_eq!((stats.targets, stats.min, stats.max), (0, 0, 0));
+        assert_eq!(stats.mean, 0.0);
+        assert_eq!(stats.histogram, vec![0]);
+    }
+
+    #[test]
+    fn histogram_bins() {
+        assert_eq!(bin_range(0), (0, 0));
+        assert_eq!(bin_range(1), (1, 1));
+        assert_eq!(bin_range(4), (8, 15));
+    }
+}
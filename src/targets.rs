@@ -0,0 +1,125 @@
+/*
+Read a list of target pages, to which counting is then restricted. Each line holds either a title
+(optionally prefixed with its namespace, e.g. “Talk:Some page”) or a page ID written as ‘id:123’.
+Empty lines and lines starting with # are ignored. Targets may also be added one by one.
+*/
+use crate::{
+    link_count::LinkCount,
+    page_table::PageIndex,
+    util::{self, PageId, PageNs, PageTitle},
+};
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result};
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct Targets {
+    pub pages: AHashSet<(PageNs, PageTitle)>,
+    pub ids: AHashSet<PageId>,
+}
+
+impl Targets {
+    pub fn read_file(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read targets file ‘{}’", path.display()))?;
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.add(line)
+                .with_context(|| format!("Invalid entry on line {} of targets file", i + 1))?;
+        }
+
+        Ok(())
+    }
+
+    // Add a single target, given by title or as ‘id:123’
+    pub fn add(&mut self, target: &str) -> Result<()> {
+        if let Some(id) = target.strip_prefix("id:") {
+            let id = id
+                .trim()
+                .parse::<u32>()
+                .with_context(|| format!("Invalid page ID ‘{}’", id))?;
+            self.ids.insert(PageId(id));
+        } else {
+            self.pages.insert(util::parse_title(target));
+        }
+
+        Ok(())
+    }
+
+    // Add titles of the pages that were listed by ID
+    pub fn resolve_ids(&mut self, index: &PageIndex) {
+        for id in self.ids.drain() {
+            match index.get(&id) {
+                Some(page) => {
+                    self.pages.insert((page.ns, page.title.clone()));
+                }
+                None => eprintln!(
+                    "Warning: target page ID {} not found in the page table",
+                    id.0
+                ),
+            }
+        }
+    }
+
+    /*
+    Replace targets that are themselves redirects with the pages they lead to, then drop all
+    redirects that don't lead to any target. Each replacement is reported, as the listed title
+    doesn't appear in the results.
+    */
+    pub fn follow_redirects(&mut self, redirects: &mut AHashMap<(PageNs, PageTitle), PageTitle>) {
+        self.pages = self
+            .pages
+            .drain()
+            .map(|page| match redirects.get(&page) {
+                Some(target) => {
+                    eprintln!(
+                        "Warning: target ‘{}’ is a redirect, counting links to ‘{}’ instead",
+                        util::display_title(page.0, &page.1),
+                        util::display_title(page.0, target)
+                    );
+                    (page.0, target.clone())
+                }
+                None => page,
+            })
+            .collect();
+
+        let pages = &self.pages;
+        redirects.retain(|(ns, _), target| pages.contains(&(*ns, target.clone())));
+    }
+
+    // Ensure every target is part of the results, even if no links to it were found
+    pub fn fill_missing(&self, pagelinks: &mut AHashMap<(PageNs, PageTitle), LinkCount>) {
+        for page in &self.pages {
+            pagelinks.entry(page.clone()).or_default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_title_and_id() {
+        let mut targets = Targets::default();
+        targets.add("Template:Infobox person").unwrap();
+        targets.add("id: 42").unwrap();
+
+        assert!(targets
+            .pages
+            .contains(&(PageNs(10), PageTitle("Infobox_person".to_string()))));
+        assert!(targets.ids.contains(&PageId(42)));
+    }
+
+    #[test]
+    fn add_invalid_id() {
+        assert!(Targets::default().add("id:abc").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+/*
+Process SQL dumps for the MediaWiki “templatelinks” table, i.e. count transclusions per template.
+Both the legacy schema (with namespace and title per row) and the newer one (referring to the
+“linktarget” table by ID) are supported.
+*/
+use crate::{
+    linktarget_table::LinkTargets,
+    pagelinks_table::{LinkCounter, LinkRow, PageLinks},
+    redirect_table::RedirectMap,
+    sql_dump::{self, RowParser},
+    util::{self, PageId, PageNs, PageTitle},
+};
+
+use ahash::AHashSet;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use std::io::Read;
+
+// If ‘linktargets’ is given, rows are expected to be in the newer, linktarget-based schema
+pub struct TemplatelinksParser {
+    regex: Regex,
+    linktargets: Option<LinkTargets>,
+}
+
+impl TemplatelinksParser {
+    pub fn new(
+        namespaces: (&[PageNs], &[PageNs]),
+        linktargets: Option<LinkTargets>,
+    ) -> Result<Self> {
+        let regex = match &linktargets {
+            Some(_) => build_templatelinks_regex(namespaces.0)?,
+            None => build_legacy_templatelinks_regex(namespaces.0, namespaces.1)?,
+        };
+        Ok(Self { regex, linktargets })
+    }
+}
+
+impl RowParser for TemplatelinksParser {
+    type Row<'a> = LinkRow<'a>;
+
+    fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    fn parse<'a>(&'a self, cap: &Captures<'a>) -> Option<LinkRow<'a>> {
+        let from = PageId(cap[1].parse::<u32>().unwrap());
+        match &self.linktargets {
+            Some(linktargets) => {
+                // Link targets are only mapped within the ‘to’ namespaces
+                let (ns, title) = linktargets.get(&cap[3].parse::<u64>().unwrap())?;
+                Some(LinkRow {
+                    from,
+                    from_ns: PageNs(cap[2].parse::<u32>().unwrap()),
+                    ns: *ns,
+                    title: title.0.as_str(),
+                })
+            }
+            None => Some(LinkRow {
+                from,
+                from_ns: PageNs(cap[4].parse::<u32>().unwrap()),
+                ns: PageNs(cap[2].parse::<u32>().unwrap()),
+                title: cap.get(3).unwrap().as_str(),
+            }),
+        }
+    }
+}
+
+// Count transclusions of templates, with those of redirects added to their target (as indirect)
+pub fn count_transclusions<T>(
+    source: T,
+    redirects: RedirectMap,
+    linktargets: Option<LinkTargets>,
+    namespaces: (&[PageNs], &[PageNs]),
+    targets: Option<&AHashSet<(PageNs, PageTitle)>>,
+    buffer_size: usize,
+) -> Result<PageLinks>
+where
+    T: Read + Send,
+{
+    let parser = TemplatelinksParser::new(namespaces, linktargets)?;
+    let counter = LinkCounter {
+        redirects: &redirects,
+        targets,
+        exclusions: None,
+        namespaces: None,
+        per_redirect: false,
+    };
+
+    let counts = sql_dump::process(
+        source,
+        &parser,
+        &counter,
+        "3/5 Extracting ‘templatelinks’ table data and counting transclusions",
+        buffer_size,
+    )?;
+
+    Ok(counts.links)
+}
+
+/*
+Regex Pattern: \((\d+),({}),(\d+)\)
+
+(\d+) : match and capture the ‘tl_from’ field.
+
+,({}), : match and capture the ‘tl_from_namespace’ on any of the given numbers (e.g. 0|5|7) passed
+via the function parameter.
+
+(\d+) : match and capture the ‘tl_target_id’ field, referring to ‘lt_id’ of the linktarget table.
+*/
+fn build_templatelinks_regex(namespaces_from: &[PageNs]) -> Result<Regex> {
+    let ns_from_str = util::namespaces_to_string(namespaces_from);
+
+    let pattern = format!(r"\((\d+),({}),(\d+)\)", ns_from_str);
+    Regex::new(&pattern).context("Building templatelinks regex")
+}
+
+/*
+Regex Pattern: \((\d+),({}),'((?:[^']|\\'){1,255}?)',({})\)
+
+Same as for the pagelinks table: captured ‘tl_from’, ‘tl_namespace’ (second namespaces parameter),
+‘tl_title’ and ‘tl_from_namespace’ (first namespaces parameter).
+*/
+fn build_legacy_templatelinks_regex(
+    namespaces_from: &[PageNs],
+    namespaces_to: &[PageNs],
+) -> Result<Regex> {
+    let ns_from_str = util::namespaces_to_string(namespaces_from);
+    let ns_to_str = util::namespaces_to_string(namespaces_to);
+
+    let pattern = format!(
+        r"\((\d+),({}),'((?:[^']|\\'){{1,255}}?)',({})\)",
+        ns_to_str, ns_from_str
+    );
+    Regex::new(&pattern).context("Building templatelinks regex")
+}
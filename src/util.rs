@@ -1,21 +1,27 @@
 /*
 Shared utility types and functions
 */
-use crate::link_count::LinkCount;
+use crate::{
+    html_report,
+    link_count::LinkCount,
+    namespace,
+    results::{Entry, Key, Results, UnsortedResults, Value},
+};
 
 use ahash::AHashMap;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
 
 use std::convert::TryFrom;
 use std::fmt;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 
-#[derive(Clone, Copy, Default, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, PartialOrd)]
 pub struct PageId(pub u32);
 
-#[derive(Clone, Copy, Default, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PageNs(pub u32);
 
 impl fmt::Display for PageNs {
@@ -24,7 +30,7 @@ impl fmt::Display for PageNs {
     }
 }
 
-#[derive(Clone, Default, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PageTitle(pub String);
 
 impl fmt::Display for PageTitle {
@@ -37,7 +43,12 @@ impl fmt::Display for PageTitle {
 pub enum ExportFormat {
     PlainText,
     WikiText,
-    Markdown
+    Markdown,
+    Json,
+    Csv,
+    Tsv,
+    Ndjson,
+    Html,
 }
 
 impl TryFrom<&str> for ExportFormat {
@@ -48,11 +59,95 @@ impl TryFrom<&str> for ExportFormat {
             "text" => Ok(Self::PlainText),
             "wiki" => Ok(Self::WikiText),
             "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "ndjson" => Ok(Self::Ndjson),
+            "html" => Ok(Self::Html),
             _ => Err(format!("Cannot convert ‘{}’ into ExportFormat", format)),
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    MostLinked,
+    Wanted,
+    Outgoing,
+    Orphans,
+    DeadEnds,
+    WhatLinksHere,
+    Templates,
+    Categories,
+    Files,
+    Domains,
+    Interwiki,
+    Disambiguation,
+    CategoryRollup,
+    Stubs,
+    RedirectCategories,
+}
+
+impl TryFrom<&str> for Mode {
+    type Error = String;
+
+    fn try_from(mode: &str) -> Result<Self, Self::Error> {
+        match mode {
+            "most-linked" => Ok(Self::MostLinked),
+            "wanted" => Ok(Self::Wanted),
+            "outgoing" => Ok(Self::Outgoing),
+            "orphans" => Ok(Self::Orphans),
+            "dead-ends" => Ok(Self::DeadEnds),
+            "what-links-here" => Ok(Self::WhatLinksHere),
+            "templates" => Ok(Self::Templates),
+            "categories" => Ok(Self::Categories),
+            "files" => Ok(Self::Files),
+            "domains" => Ok(Self::Domains),
+            "interwiki" => Ok(Self::Interwiki),
+            "disambiguation" => Ok(Self::Disambiguation),
+            "category-rollup" => Ok(Self::CategoryRollup),
+            "stubs" => Ok(Self::Stubs),
+            "redirect-categories" => Ok(Self::RedirectCategories),
+            _ => Err(format!("Cannot convert ‘{}’ into Mode", mode)),
+        }
+    }
+}
+
+impl Mode {
+    // Name as given on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MostLinked => "most-linked",
+            Self::Wanted => "wanted",
+            Self::Outgoing => "outgoing",
+            Self::Orphans => "orphans",
+            Self::DeadEnds => "dead-ends",
+            Self::WhatLinksHere => "what-links-here",
+            Self::Templates => "templates",
+            Self::Categories => "categories",
+            Self::Files => "files",
+            Self::Domains => "domains",
+            Self::Interwiki => "interwiki",
+            Self::Disambiguation => "disambiguation",
+            Self::CategoryRollup => "category-rollup",
+            Self::Stubs => "stubs",
+            Self::RedirectCategories => "redirect-categories",
+        }
+    }
+}
+
+// Parameters of a run, for export formats that carry them along with the results
+pub struct RunInfo {
+    pub mode: Mode,
+    // Input files, by table (e.g. ‘pagelinks’)
+    pub inputs: Vec<(&'static str, PathBuf)>,
+    pub namespaces_from: Vec<PageNs>,
+    pub namespaces_to: Vec<PageNs>,
+    pub cutoff: u32,
+    // Base URL of page links, e.g. ‘https://en.wikipedia.org/wiki/’
+    pub wiki_url: String,
+}
+
 pub fn is_probably_gzip(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
         if ext == "gz" || ext == "gzip" {
@@ -62,6 +157,18 @@ pub fn is_probably_gzip(path: &Path) -> bool {
     false
 }
 
+// Open a (possibly gzip compressed) SQL dump; ‘table’ only serves the error message
+pub fn open_dump(path: &Path, table: &str) -> Result<Box<dyn Read + Send>> {
+    let f = File::open(path)
+        .with_context(|| format!("Failed to open {} file ‘{}’", table, path.display()))?;
+
+    if is_probably_gzip(path) {
+        Ok(Box::new(GzDecoder::new(f)))
+    } else {
+        Ok(Box::new(f))
+    }
+}
+
 pub fn build_output_filename(path: &Path, export_format: ExportFormat) -> PathBuf {
     use ExportFormat::*;
     let mut filename = path.to_path_buf();
@@ -81,6 +188,21 @@ pub fn build_output_filename(path: &Path, export_format: ExportFormat) -> PathBu
         Markdown => {
             filename.set_extension("md");
         },
+        Json => {
+            filename.set_extension("json");
+        },
+        Csv => {
+            filename.set_extension("csv");
+        },
+        Tsv => {
+            filename.set_extension("tsv");
+        },
+        Ndjson => {
+            filename.set_extension("ndjson");
+        },
+        Html => {
+            filename.set_extension("html");
+        },
     }
 
     filename
@@ -96,7 +218,7 @@ pub fn namespaces_to_string(namespaces: &[PageNs]) -> String {
         // In the case of multi-digit numbers, matching in
         // descending order allows more efficient regex logic
         let mut namespaces = namespaces.to_vec();
-        namespaces.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+        namespaces.sort_unstable_by_key(|ns| std::cmp::Reverse(ns.0));
 
         for ns in namespaces {
             write!(&mut ns_str, "{}|", ns.0).unwrap();
@@ -107,7 +229,47 @@ pub fn namespaces_to_string(namespaces: &[PageNs]) -> String {
     ns_str
 }
 
-fn underscores_to_spaces(mut s: String) -> String {
+/*
+Turn a title as written by a user (e.g. “Talk:Some page”) into the form used in the SQL dumps:
+namespace split off, underscores for spaces, capitalized first letter and SQL escape sequences.
+*/
+pub fn parse_title(input: &str) -> (PageNs, PageTitle) {
+    let input = input.trim();
+    let (ns, title) = match input.find(':') {
+        Some(pos) => match namespace::from_prefix(&input[..pos]) {
+            Some(ns) => (ns, &input[pos + 1..]),
+            None => (PageNs(0), input),
+        },
+        None => (PageNs(0), input),
+    };
+
+    let mut normalized = String::with_capacity(title.len());
+    for (i, c) in title.trim().chars().enumerate() {
+        match c {
+            ' ' => normalized.push('_'),
+            '\\' | '\'' | '"' => {
+                normalized.push('\\');
+                normalized.push(c);
+            }
+            _ if i == 0 => normalized.extend(c.to_uppercase()),
+            _ => normalized.push(c),
+        }
+    }
+
+    (ns, PageTitle(normalized))
+}
+
+// Full title for display, i.e. with namespace prefix (if known) and spaces
+pub fn display_title(ns: PageNs, title: &PageTitle) -> String {
+    let title = underscores_to_spaces(sql_unescape(&title.0));
+    match namespace::name(ns) {
+        Some("") => title,
+        Some(prefix) => format!("{}:{}", prefix, title),
+        None => format!("{}:{}", ns, title),
+    }
+}
+
+pub fn underscores_to_spaces(mut s: String) -> String {
     unsafe {
         for c in s.as_bytes_mut() {
             if *c == b'_' {
@@ -118,101 +280,605 @@ fn underscores_to_spaces(mut s: String) -> String {
     s
 }
 
-pub fn sort_pagelinks(
-    pagelinks: AHashMap<(PageNs, PageTitle), LinkCount>,
+// Undo the escape sequences of SQL dump strings, e.g. ‘O\'Brien’ → ‘O'Brien’
+pub fn sql_unescape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => output.push('\0'),
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('t') => output.push('\t'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+// Pages with link count above threshold, in no particular order
+pub fn filter_pagelinks<K>(
+    pagelinks: AHashMap<K, LinkCount>,
     cutoff: u32,
-) -> Vec<((PageNs, PageTitle), LinkCount)> {
-    let mut output: Vec<((PageNs, PageTitle), LinkCount)> = pagelinks
+) -> impl Iterator<Item = (K, LinkCount)> {
+    pagelinks
         .into_iter()
-        .filter(|pl| pl.1.total() >= cutoff)
-        .collect();
+        .filter(move |pl| pl.1.total() >= cutoff)
+}
 
-    output.sort_unstable_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+// Pages with link count above threshold, by link count, then by key (so that ranks are stable)
+pub fn sort_pagelinks<K: Ord>(
+    pagelinks: AHashMap<K, LinkCount>,
+    cutoff: u32,
+) -> Vec<(K, LinkCount)> {
+    let mut output: Vec<_> = filter_pagelinks(pagelinks, cutoff).collect();
+    output.sort_unstable_by(|a, b| (b.1, &a.0).cmp(&(a.1, &b.0)));
     output
 }
 
 pub fn export_to_file(
-    pages: Vec<((PageNs, PageTitle), LinkCount)>,
-    mut file: File,
+    results: Results,
+    file: File,
     format: ExportFormat,
+    run_info: &RunInfo,
 ) -> Result<()> {
     use ExportFormat::*;
+    let mut file = BufWriter::new(file);
 
     match format {
-        PlainText => write_plaintext(&mut file, pages)?,
-        WikiText => write_wikitext(&mut file, pages)?,
-        Markdown => write_markdown(&mut file, pages)?,
+        PlainText => write_plaintext(&mut file, results)?,
+        WikiText => write_wikitext(&mut file, results)?,
+        Markdown => write_markdown(&mut file, results)?,
+        Json => write_json(&mut file, results, run_info)?,
+        Csv => write_delimited(&mut file, results, ',')?,
+        Tsv => write_delimited(&mut file, results, '\t')?,
+        Ndjson => write_ndjson(&mut file, results.entries.into_iter(), &results.columns)?,
+        Html => html_report::write_html(&mut file, results, run_info)?,
     }
+    file.flush()?;
 
     Ok(())
 }
 
-fn write_plaintext(file: &mut File, pages: Vec<((PageNs, PageTitle), LinkCount)>) -> Result<()> {
-    writeln!(
-        file,
-        "page title [namespace]  →  links-total (direct + indirect)\n"
-    )?;
-    for p in pages {
-        let title = underscores_to_spaces(((p.0).1).0);
-        writeln!(
+// Write unsorted rows as NDJSON, the only format supported for these
+pub fn export_unsorted_to_file(results: UnsortedResults, file: File) -> Result<()> {
+    let mut file = BufWriter::new(file);
+    write_ndjson(&mut file, results.entries, &results.columns)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+fn write_plaintext(file: &mut impl Write, results: Results) -> Result<()> {
+    match results.name_heading {
+        Some(heading) => write!(file, "{}", heading)?,
+        None => write!(
             file,
-            "{} [{}]  →  {} ({} + {})",
-            title,
-            (p.0).0,
-            p.1.total(),
-            p.1.direct,
-            p.1.indirect
-        )?;
+            "page title [namespace]  →  links-total (direct + indirect)"
+        )?,
+    }
+    for column in &results.columns {
+        write!(file, "  |  {}", column)?;
+    }
+    writeln!(file, "\n")?;
+
+    for e in results.entries {
+        match e.key {
+            Key::Page((ns, title)) => write!(
+                file,
+                "{} [{}]  →  {} ({} + {})",
+                sql_unescape(&underscores_to_spaces(title.0)),
+                ns,
+                e.links.total(),
+                e.links.direct,
+                e.links.indirect
+            )?,
+            Key::Name(name) => write!(file, "{}", sql_unescape(&name))?,
+        }
+        for value in &e.extra {
+            write!(file, "  |  {}", value)?;
+        }
+        writeln!(file)?;
     }
     Ok(())
 }
 
-fn write_wikitext(file: &mut File, pages: Vec<((PageNs, PageTitle), LinkCount)>) -> Result<()> {
+fn write_wikitext(file: &mut impl Write, results: Results) -> Result<()> {
     writeln!(file, "{{|class=\"wikitable sortable\"")?;
-    writeln!(
-        file,
-        "! Page !! Ns !! Links total !! Direct !! via redirect\n|-"
-    )?;
-
-    for p in pages {
-        let title = underscores_to_spaces(((p.0).1).0);
-        writeln!(
+    match results.name_heading {
+        Some(heading) => write!(file, "! {}", heading)?,
+        None => write!(
             file,
-            "| [[{}]] || {} || {} || {} || {}\n|-",
-            title,
-            (p.0).0,
-            p.1.total(),
-            p.1.direct,
-            p.1.indirect
-        )?;
+            "! Page !! Ns !! Links total !! Direct !! via redirect"
+        )?,
+    }
+    for column in &results.columns {
+        write!(file, " !! {}", column)?;
+    }
+    writeln!(file, "\n|-")?;
+
+    for e in results.entries {
+        match e.key {
+            Key::Page((ns, title)) => write!(
+                file,
+                "| [[{}]] || {} || {} || {} || {}",
+                sql_unescape(&underscores_to_spaces(title.0)),
+                ns,
+                e.links.total(),
+                e.links.direct,
+                e.links.indirect
+            )?,
+            Key::Name(name) => write!(file, "| {}", sql_unescape(&name))?,
+        }
+        for value in &e.extra {
+            write!(file, " || {}", value)?;
+        }
+        writeln!(file, "\n|-")?;
     }
 
     writeln!(file, "|}}")?;
     Ok(())
 }
 
-fn write_markdown(file: &mut File, pages: Vec<((PageNs, PageTitle), LinkCount)>) -> Result<()> {
+fn write_markdown(file: &mut impl Write, results: Results) -> Result<()> {
     // NOTE: Markdown tables are non-standard (GitHub Flavored Markdown); This function also
     // doesn't pretty-print the table, which would require significantly more logic.
+    match results.name_heading {
+        Some(heading) => write!(file, "{}", heading)?,
+        None => write!(file, "Page | Ns | Links total | Direct | via redirect")?,
+    }
+    for column in &results.columns {
+        write!(file, " | {}", column)?;
+    }
+    match results.name_heading {
+        Some(_) => write!(file, "\n:---")?,
+        None => write!(file, "\n:--- | :---: | ---: | ---: | ---:")?,
+    }
+    for _ in &results.columns {
+        write!(file, " | :---:")?;
+    }
+    writeln!(file)?;
+
+    for e in results.entries {
+        match e.key {
+            Key::Page((ns, title)) => write!(
+                file,
+                "{} | {} | {} | {} | {}",
+                sql_unescape(&underscores_to_spaces(title.0)),
+                ns,
+                e.links.total(),
+                e.links.direct,
+                e.links.indirect
+            )?,
+            Key::Name(name) => write!(file, "{}", sql_unescape(&name))?,
+        }
+        for value in &e.extra {
+            write!(file, " | {}", value)?;
+        }
+        writeln!(file)?;
+    }
+
+    Ok(())
+}
+
+/*
+A single JSON document: run parameters under ‘metadata’, then one object per row under ‘results’.
+Page rows carry their namespace, title and link counts; rows keyed by name carry the name only.
+Additional columns, if any, go into an ‘extra’ object keyed by column heading.
+*/
+fn write_json(file: &mut impl Write, results: Results, run_info: &RunInfo) -> Result<()> {
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"metadata\": {{")?;
+    writeln!(file, "    \"mode\": {},", json_string(run_info.mode.name()))?;
+    writeln!(file, "    \"inputs\": {{")?;
+    for (i, (table, path)) in run_info.inputs.iter().enumerate() {
+        let separator = if i + 1 < run_info.inputs.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            file,
+            "      {}: {}{}",
+            json_string(table),
+            json_string(&path.display().to_string()),
+            separator
+        )?;
+    }
+    writeln!(file, "    }},")?;
     writeln!(
         file,
-        "Page | Ns | Links total | Direct | via redirect\n\
-        :--- | :---: | ---: | ---: | ---:"
+        "    \"namespaces_from\": [{}],",
+        json_namespaces(&run_info.namespaces_from)
     )?;
+    writeln!(
+        file,
+        "    \"namespaces_to\": [{}],",
+        json_namespaces(&run_info.namespaces_to)
+    )?;
+    writeln!(file, "    \"cutoff\": {}", run_info.cutoff)?;
+    writeln!(file, "  }},")?;
+
+    writeln!(file, "  \"results\": [")?;
+    let count = results.entries.len();
+    for (i, e) in results.entries.into_iter().enumerate() {
+        write!(file, "    ")?;
+        write_json_entry(file, e, &results.columns)?;
+        writeln!(file, "{}", if i + 1 < count { "," } else { "" })?;
+    }
+    writeln!(file, "  ]")?;
+    writeln!(file, "}}")?;
+    Ok(())
+}
 
-    for p in pages {
-        let title = underscores_to_spaces(((p.0).1).0);
-        writeln!(
+// Write a single row as JSON object, on one line
+fn write_json_entry(file: &mut impl Write, e: Entry, columns: &[&str]) -> Result<()> {
+    match e.key {
+        Key::Page((ns, title)) => write!(
             file,
-            "{} | {} | {} | {} | {}",
-            title,
-            (p.0).0,
-            p.1.total(),
-            p.1.direct,
-            p.1.indirect
-        )?;
+            "{{ \"namespace\": {}, \"title\": {}, \"total\": {}, \"direct\": {}, \
+            \"indirect\": {}",
+            ns,
+            json_string(&sql_unescape(&underscores_to_spaces(title.0))),
+            e.links.total(),
+            e.links.direct,
+            e.links.indirect
+        )?,
+        Key::Name(name) => write!(file, "{{ \"name\": {}", json_string(&sql_unescape(&name)))?,
+    }
+    if !columns.is_empty() {
+        write!(file, ", \"extra\": {{ ")?;
+        for (i, (column, value)) in columns.iter().zip(&e.extra).enumerate() {
+            let separator = if i > 0 { ", " } else { "" };
+            write!(
+                file,
+                "{}{}: {}",
+                separator,
+                json_string(column),
+                json_value(value)
+            )?;
+        }
+        write!(file, " }}")?;
     }
+    write!(file, " }}")?;
+    Ok(())
+}
 
+/*
+Newline delimited JSON: one object per row, as within the ‘results’ of the JSON format, without any
+run parameters. Rows are written as they come, so they may be streamed into other tools.
+*/
+fn write_ndjson(
+    file: &mut impl Write,
+    entries: impl Iterator<Item = Entry>,
+    columns: &[&str],
+) -> Result<()> {
+    for e in entries {
+        write_json_entry(file, e, columns)?;
+        writeln!(file)?;
+    }
+    Ok(())
+}
+
+fn json_namespaces(namespaces: &[PageNs]) -> String {
+    let namespaces: Vec<String> = namespaces.iter().map(|ns| ns.to_string()).collect();
+    namespaces.join(", ")
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => b.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(x) if x.is_finite() => x.to_string(),
+        Value::Text(s) => json_string(s),
+        Value::Float(_) | Value::Empty => "null".to_string(),
+    }
+}
+
+// Quoted JSON string, with quotes, backslashes and control characters escaped
+fn json_string(s: &str) -> String {
+    use std::fmt::Write;
+
+    let mut output = String::with_capacity(s.len() + 2);
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => write!(&mut output, "\\u{:04x}", c as u32).unwrap(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/*
+One header row, then one row per entry: namespace, title, link counts and rank (i.e. position), or
+name and rank for rows keyed by name, followed by any additional columns. Comma separated fields
+are quoted as per RFC 4180; tab separated ones can't be quoted, so tabs and line breaks within
+them are replaced by spaces.
+*/
+fn write_delimited(file: &mut impl Write, results: Results, delimiter: char) -> Result<()> {
+    let line_end = if delimiter == ',' { "\r\n" } else { "\n" };
+    let field = |s: &str| delimited_field(s, delimiter);
+
+    let mut header: Vec<String> = match results.name_heading {
+        Some(heading) => vec![field(heading), "rank".to_string()],
+        None => ["namespace", "title", "total", "direct", "indirect", "rank"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect(),
+    };
+    header.extend(results.columns.iter().map(|column| field(column)));
+    write!(file, "{}{}", header.join(&delimiter.to_string()), line_end)?;
+
+    for (i, e) in results.entries.into_iter().enumerate() {
+        let mut row = match e.key {
+            Key::Page((ns, title)) => vec![
+                ns.to_string(),
+                field(&sql_unescape(&underscores_to_spaces(title.0))),
+                e.links.total().to_string(),
+                e.links.direct.to_string(),
+                e.links.indirect.to_string(),
+            ],
+            Key::Name(name) => vec![field(&sql_unescape(&name))],
+        };
+        row.push((i + 1).to_string());
+        for value in &e.extra {
+            row.push(match value {
+                Value::Bool(b) => b.to_string(),
+                Value::Float(x) => x.to_string(),
+                _ => field(&value.to_string()),
+            });
+        }
+        write!(file, "{}{}", row.join(&delimiter.to_string()), line_end)?;
+    }
     Ok(())
 }
 
+fn delimited_field(s: &str, delimiter: char) -> String {
+    if delimiter != ',' {
+        return s.replace(&[delimiter, '\n', '\r'][..], " ");
+    }
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::Columns;
+
+    #[test]
+    fn parse_title_main_namespace() {
+        assert_eq!(
+            parse_title("  some page "),
+            (PageNs(0), PageTitle("Some_page".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_title_namespace_prefix() {
+        assert_eq!(
+            parse_title("Talk:some page"),
+            (PageNs(1), PageTitle("Some_page".to_string()))
+        );
+        assert_eq!(
+            parse_title("category: Fruit"),
+            (PageNs(14), PageTitle("Fruit".to_string()))
+        );
+        assert_eq!(
+            parse_title("WP:Village pump"),
+            (PageNs(4), PageTitle("Village_pump".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_title_unknown_prefix_is_part_of_title() {
+        assert_eq!(
+            parse_title("Star Wars: A New Hope"),
+            (PageNs(0), PageTitle("Star_Wars:_A_New_Hope".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_title_escapes_quotes() {
+        assert_eq!(
+            parse_title("O'Brien \"Jr\""),
+            (PageNs(0), PageTitle("O\\'Brien_\\\"Jr\\\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn sql_unescape_sequences() {
+        assert_eq!(sql_unescape("O\\'Brien"), "O'Brien");
+        assert_eq!(sql_unescape("a\\nb\\tc\\\\d"), "a\nb\tc\\d");
+        assert_eq!(sql_unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn display_title_unescapes() {
+        let title = PageTitle("O\\'Brien_(actor)".to_string());
+        assert_eq!(display_title(PageNs(0), &title), "O'Brien (actor)");
+        assert_eq!(display_title(PageNs(1), &title), "Talk:O'Brien (actor)");
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("plain, text"), "\"plain, text\"");
+        assert_eq!(json_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(json_string("back\\slash"), "\"back\\\\slash\"");
+        assert_eq!(
+            json_string("two\nlines\tand tab"),
+            "\"two\\nlines\\tand tab\""
+        );
+        assert_eq!(
+            json_string("\r\u{0}\u{1f}\u{7f}"),
+            "\"\\u000d\\u0000\\u001f\\u007f\""
+        );
+        assert_eq!(json_string("Zürich ‘quoted’"), "\"Zürich ‘quoted’\"");
+    }
+
+    #[test]
+    fn json_values() {
+        assert_eq!(json_value(&Value::Bool(true)), "true");
+        assert_eq!(json_value(&Value::Int(42)), "42");
+        assert_eq!(json_value(&Value::Float(0.5)), "0.5");
+        assert_eq!(json_value(&Value::Float(f64::NAN)), "null");
+        assert_eq!(json_value(&Value::Empty), "null");
+        assert_eq!(json_value(&Value::Text("a\"b".to_string())), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn json_entries() {
+        let write = |e: Entry, columns: &[&str]| {
+            let mut output = Vec::new();
+            write_json_entry(&mut output, e, columns).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let page = Entry {
+            key: Key::Page((PageNs(1), PageTitle("O\\'Brien,_\\\"Jr\\\"".to_string()))),
+            links: LinkCount::new(2, 1),
+            extra: vec![Value::Text("line\nbreak".to_string()), Value::Empty],
+        };
+        assert_eq!(
+            write(page, &["Note", "Size"]),
+            "{ \"namespace\": 1, \"title\": \"O'Brien, \\\"Jr\\\"\", \"total\": 3, \
+            \"direct\": 2, \"indirect\": 1, \"extra\": { \"Note\": \"line\\nbreak\", \
+            \"Size\": null } }"
+        );
+
+        let name = Entry {
+            key: Key::Name("fr:L\\'Été".to_string()),
+            links: LinkCount::default(),
+            extra: Vec::new(),
+        };
+        assert_eq!(write(name, &[]), "{ \"name\": \"fr:L'Été\" }");
+    }
+
+    #[test]
+    fn json_document() {
+        let run_info = RunInfo {
+            mode: Mode::MostLinked,
+            inputs: vec![("page", PathBuf::from("dumps/\"page\".sql"))],
+            namespaces_from: vec![PageNs(0), PageNs(1)],
+            namespaces_to: Vec::new(),
+            cutoff: 5,
+            wiki_url: String::new(),
+        };
+        let page = |title: &str| (PageNs(0), PageTitle(title.to_string()));
+        let results = Results::new(vec![
+            (page("A"), LinkCount::new(1, 0)),
+            (page("B"), LinkCount::new(0, 1)),
+        ]);
+
+        let mut output = Vec::new();
+        write_json(&mut output, results, &run_info).unwrap();
+        let lines: Vec<String> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect();
+        assert!(lines.contains(&"\"page\": \"dumps/\\\"page\\\".sql\"".to_string()));
+        assert!(lines.contains(&"\"namespaces_from\": [0, 1],".to_string()));
+        assert!(lines.contains(&"\"namespaces_to\": [],".to_string()));
+        // Rows are separated by commas, without one after the last
+        let rows: Vec<&String> = lines.iter().filter(|l| l.starts_with("{ ")).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].ends_with("},"));
+        assert!(rows[1].ends_with('}'));
+    }
+
+    #[test]
+    fn unsorted_ndjson_matches_sorted() {
+        let page = |title: &str| (PageNs(0), PageTitle(title.to_string()));
+        let pagelinks: AHashMap<_, _> = vec![
+            (page("A"), LinkCount::new(1, 0)),
+            (page("B"), LinkCount::new(2, 1)),
+            (page("C"), LinkCount::new(0, 0)),
+        ]
+        .into_iter()
+        .collect();
+        let columns = || {
+            let mut columns = Columns::default();
+            columns.annotate("Long", |(_, title), _| Value::Bool(title.0.len() > 1));
+            columns
+        };
+
+        let mut sorted = Vec::new();
+        let mut results = Results::new(sort_pagelinks(pagelinks.clone(), 1));
+        results.add_columns(columns());
+        write_ndjson(&mut sorted, results.entries.into_iter(), &results.columns).unwrap();
+
+        let mut unsorted = Vec::new();
+        let results = UnsortedResults::new(filter_pagelinks(pagelinks, 1)).add_columns(columns());
+        write_ndjson(&mut unsorted, results.entries, &results.columns).unwrap();
+
+        let lines = |output: Vec<u8>| {
+            let mut lines: Vec<String> = String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect();
+            lines.sort_unstable();
+            lines
+        };
+        let unsorted = lines(unsorted);
+        assert_eq!(unsorted.len(), 2);
+        assert!(unsorted[0].ends_with("\"extra\": { \"Long\": false } }"));
+        assert_eq!(unsorted, lines(sorted));
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(delimited_field("plain text", ','), "plain text");
+        assert_eq!(delimited_field("a,b", ','), "\"a,b\"");
+        assert_eq!(delimited_field("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(delimited_field("two\nlines", ','), "\"two\nlines\"");
+        assert_eq!(delimited_field("cr\r", ','), "\"cr\r\"");
+        assert_eq!(delimited_field("tab\tkept", ','), "tab\tkept");
+    }
+
+    #[test]
+    fn tsv_fields() {
+        assert_eq!(delimited_field("a,\"b\"", '\t'), "a,\"b\"");
+        assert_eq!(delimited_field("tab\there", '\t'), "tab here");
+        assert_eq!(delimited_field("two\r\nlines", '\t'), "two  lines");
+    }
+
+    #[test]
+    fn ranks_follow_title_on_ties() {
+        let page = |title: &str| (PageNs(0), PageTitle(title.to_string()));
+        let pagelinks: AHashMap<_, _> = vec![
+            (page("C"), LinkCount::new(1, 0)),
+            (page("B"), LinkCount::new(0, 2)),
+            (page("A"), LinkCount::new(1, 0)),
+            (page("D"), LinkCount::new(0, 0)),
+        ]
+        .into_iter()
+        .collect();
+        let results = Results::new(sort_pagelinks(pagelinks, 1));
+
+        let mut output = Vec::new();
+        write_delimited(&mut output, results, ',').unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "namespace,title,total,direct,indirect,rank\r\n\
+            0,B,2,0,2,1\r\n\
+            0,A,1,1,0,2\r\n\
+            0,C,1,1,0,3\r\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/*
+End-to-end runs on small dumps, for behaviour which depends on how modes and options combine.
+*/
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const PAGE: &str = "INSERT INTO `page` VALUES \
+    (1,0,'Apple','',0,0,0.5,'20200501000000','20200501000000',100,1200,'wikitext',NULL),\
+    (2,0,'Pear','',0,0,0.5,'20200501000000','20200501000000',101,300,'wikitext',NULL),\
+    (3,0,'Orchard','',0,0,0.5,'20200501000000','20200501000000',102,5000,'wikitext',NULL);\n";
+
+const REDIRECT: &str = "INSERT INTO `redirect` VALUES (0,0,'None','','');\n";
+
+const PAGELINKS: &str = "INSERT INTO `pagelinks` VALUES \
+    (2,0,'Apple',0),(3,0,'Apple',0),(3,0,'Missing_page',0),(2,0,'Orchard',0);\n\
+    INSERT INTO `pagelinks` VALUES (3,0,'Pear',0),(1,0,'Apple',0);\n";
+
+// Write the given dumps into a fresh directory, named after the test
+fn dumps(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wikidigest-link-count-{}-{}", test, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (name, content) in files {
+        fs::write(dir.join(name), content).unwrap();
+    }
+    dir
+}
+
+// Run with the given arguments, returning the CSV output, or the error message on failure
+fn run(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_wikidigest-link-count"))
+        .current_dir(dir)
+        .args(args)
+        .args(["-e", "csv", "-o", "out"])
+        .output()
+        .unwrap();
+
+    if output.status.success() {
+        Ok(fs::read_to_string(dir.join("out.csv")).unwrap())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+fn links_dumps(test: &str) -> PathBuf {
+    dumps(
+        test,
+        &[
+            ("page.sql", PAGE),
+            ("redirect.sql", REDIRECT),
+            ("pagelinks.sql", PAGELINKS),
+        ],
+    )
+}
+
+const PAGE_ARGS: &[&str] = &["-p", "page.sql", "-r", "redirect.sql"];
+
+#[test]
+fn wanted_targets_skip_existing_pages() {
+    let dir = links_dumps("wanted-targets");
+    let targets = [
+        "--target",
+        "Apple",
+        "--target",
+        "Missing page",
+        "--target",
+        "Never linked",
+    ];
+    let args = [
+        PAGE_ARGS,
+        &["-l", "pagelinks.sql", "-m", "wanted"],
+        &targets,
+    ]
+    .concat();
+
+    let output = run(&dir, &args).unwrap();
+    let rows: Vec<&str> = output.lines().skip(1).collect();
+    assert_eq!(rows, ["0,Missing page,1,1,0,1", "0,Never linked,0,0,0,2"]);
+}
+
+#[test]
+fn category_rollup_rejects_targets() {
+    let dir = links_dumps("rollup-targets");
+    let args = [
+        PAGE_ARGS,
+        &["-l", "pagelinks.sql", "-m", "category-rollup"],
+        &["--categorylinks-file", "categorylinks.sql"],
+        &["--target", "Pear"],
+    ]
+    .concat();
+
+    let error = run(&dir, &args).unwrap_err();
+    assert!(error.contains("Mode ‘category-rollup’ doesn't support targets"));
+}
+
+#[test]
+fn stubs_min_links_ignores_cutoff() {
+    let dir = links_dumps("stubs-min-links");
+    let stubs = [PAGE_ARGS, &["-l", "pagelinks.sql", "-m", "stubs"]].concat();
+
+    let output = run(&dir, &[&stubs[..], &["--min-links", "2"]].concat()).unwrap();
+    let rows: Vec<&str> = output.lines().skip(1).collect();
+    assert_eq!(rows, ["0,Apple,3,3,0,1,1200,2.56"]);
+
+    // The default minimum still applies with a lower cutoff
+    let output = run(&dir, &[&stubs[..], &["-c", "0"]].concat()).unwrap();
+    assert_eq!(output.lines().count(), 1);
+}